@@ -9,8 +9,14 @@ fn main() {
     let mut file = File::create(&Path::new(&dest).join("gl_bindings.rs")).unwrap();
 
     // https://registry.khronos.org/OpenGL/extensions/ARB/ARB_bindless_texture.txt
+    // https://registry.khronos.org/OpenGL/extensions/EXT/EXT_texture_compression_s3tc.txt
+    // https://registry.khronos.org/OpenGL/extensions/EXT/EXT_texture_sRGB.txt
     
-	Registry::new(Api::Gl, (4, 6), Profile::Core, Fallbacks::All, ["GL_ARB_bindless_texture"])
+	Registry::new(Api::Gl, (4, 6), Profile::Core, Fallbacks::All, [
+			"GL_ARB_bindless_texture",
+			"GL_EXT_texture_compression_s3tc",
+			"GL_EXT_texture_sRGB",
+		])
 	    .write_bindings(GlobalGenerator, &mut file)
 	    .unwrap();
 }
\ No newline at end of file
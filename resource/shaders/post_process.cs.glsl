@@ -4,22 +4,7 @@ layout(binding = 0, r11f_g11f_b10f) uniform image2D u_image;
 
 
 
-vec3 rgb_to_yuv(in vec3 rgb){
-	float y = 0.299*rgb.r + 0.587*rgb.g + 0.114*rgb.b;
-	return vec3(y, 0.493*(rgb.b-y), 0.877*(rgb.r-y));
-}
-
-vec3 yuv_to_rgb(in vec3 yuv){
-	float y = yuv.x;
-	float u = yuv.y;
-	float v = yuv.z;
-	
-	return vec3(
-		y + 1.0/0.877*v,
-		y - 0.39393*u - 0.58081*v,
-		y + 1.0/0.493*u
-	);
-}
+#include "common/color.glsl"
 
 
 shared vec3 s_samples[2][gl_WorkGroupSize.x*gl_WorkGroupSize.y];
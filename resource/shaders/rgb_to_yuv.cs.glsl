@@ -5,10 +5,7 @@ layout(binding = 1, rgba16f) uniform image2D u_yuv_image;
 
 
 
-vec3 rgb_to_yuv(in vec3 rgb){
-	float y = 0.299*rgb.r + 0.587*rgb.g + 0.114*rgb.b;
-	return vec3(y, 0.493*(rgb.b-y), 0.877*(rgb.r-y));
-}
+#include "common/color.glsl"
 
 
 
@@ -1,3 +1,5 @@
+#pragma stage(vertex)
+
 out gl_PerVertex {
     vec4 gl_Position;
 };
@@ -34,4 +36,19 @@ void main() {
 
     v_color = u_color;
     v_uv = position * 0.5 + vec2(0.5);
-}
\ No newline at end of file
+}
+
+
+#pragma stage(fragment)
+
+in vec4 v_color;
+in vec2 v_uv;
+
+layout(binding=5) uniform sampler2D u_texture;
+
+out vec4 o_color;
+
+void main() {
+	vec4 color = texture(u_texture, v_uv);
+	o_color = v_color * color;
+}
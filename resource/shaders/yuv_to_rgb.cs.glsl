@@ -4,17 +4,7 @@ layout(binding = 0, rgba16f) uniform readonly image2D u_yuv_image;
 layout(binding = 1, r11f_g11f_b10f) uniform image2D u_rgb_image;
 
 
-vec3 yuv_to_rgb(in vec3 yuv){
-	float y = yuv.x;
-	float u = yuv.y;
-	float v = yuv.z;
-	
-	return vec3(
-		y + 1.0/0.877*v,
-		y - 0.39393*u - 0.58081*v,
-		y + 1.0/0.493*u
-	);
-}
+#include "common/color.glsl"
 
 
 void main() {
@@ -2,8 +2,9 @@ mod pass;
 mod draw_cmd;
 mod dispatch_cmd;
 
-use crate::resource_manager::{ShaderHandle, BlockBindingLocation, ImageHandle, SamplerDef, PipelineDef};
+use crate::resource_manager::{ShaderHandle, BlockBindingLocation, ImageHandle, SamplerDef, PipelineDef, ImageBindingKind, ProgramHandle, ImageRegion};
 use crate::upload_heap::{UploadHeap, BufferAllocation, UPLOAD_BUFFER_SIZE};
+use crate::readback::{ReadbackRequest, ReadbackSource, ReadbackTicket};
 
 pub use pass::*;
 pub use draw_cmd::*;
@@ -52,6 +53,23 @@ impl Command {
 		}
 	}
 
+	pub fn shader_features_mut(&mut self) -> &mut Vec<&'static str> {
+		match self {
+			Command::Draw(DrawCmd { features, .. })
+			| Command::Dispatch(DispatchCmd { features, .. })
+				=> features,
+		}
+	}
+
+	pub fn shaders_mut(&mut self) -> impl Iterator<Item=&mut ShaderHandle> {
+		let (first, second) = match self {
+			Command::Draw(cmd) => (&mut cmd.vertex_shader, cmd.fragment_shader.as_mut()),
+			Command::Dispatch(cmd) => (&mut cmd.compute_shader, None),
+		};
+
+		std::iter::once(first).chain(second)
+	}
+
 	pub fn pipeline_def(&self) -> Option<PipelineDef> {
 		match self {
 			Command::Draw(cmd) => Some(PipelineDef {
@@ -80,13 +98,30 @@ pub struct TransientAllocator {
 pub struct FrameState {
 	pub passes: Vec<Pass>,
 	pub allocator: TransientAllocator,
+	pub image_uploads: Vec<ImageUpload>,
+	pub readbacks: Vec<ReadbackRequest>,
+
+	next_readback_ticket: u64,
+}
 
+#[derive(Debug)]
+pub struct ImageUpload {
+	pub image: ImageHandle,
+	pub region: ImageRegion,
+	pub buffer: BufferHandle,
+
+	/// Size of the data in `buffer`, so it can be validated before buffers are allocated.
+	pub byte_size: usize,
 }
 
 impl FrameState {
 	pub fn new() -> Self {
 		FrameState {
 			passes: Vec::new(),
+			image_uploads: Vec::new(),
+			readbacks: Vec::new(),
+
+			next_readback_ticket: 0,
 
 			allocator: TransientAllocator {
 				transient_data: bumpalo::Bump::with_capacity(UPLOAD_BUFFER_SIZE),
@@ -99,6 +134,8 @@ impl FrameState {
 	pub fn reset(&mut self) {
 		// self.commands.clear();
 		self.passes.clear();
+		self.image_uploads.clear();
+		self.readbacks.clear();
 		self.allocator.reserved_buffers.clear();
 		self.allocator.streamed_buffers.clear();
 		self.allocator.transient_data.reset();
@@ -125,6 +162,36 @@ impl FrameState {
 		BufferHandle::Streamed(index)
 	}
 
+	/// Updates part of `image` before any pass runs this frame. `data` must be tightly packed texels in the image's format.
+	/// Staged through the upload heap, so it has to fit in UPLOAD_BUFFER_SIZE along with everything else streamed this frame.
+	pub fn upload_image_region<T>(&mut self, image: ImageHandle, region: ImageRegion, data: &[T])
+		where T: Copy
+	{
+		let byte_size = data.len() * std::mem::size_of::<T>();
+		assert!(byte_size <= UPLOAD_BUFFER_SIZE, "Image region of {byte_size} bytes won't fit in the upload heap");
+
+		let buffer = self.stream_buffer(data);
+		self.image_uploads.push(ImageUpload { image, region, buffer, byte_size });
+	}
+
+	/// Copies the base level of `image` back to the CPU once every pass this frame has run.
+	/// Collect the result with `Context::poll_readback` or `Context::wait_readback`.
+	pub fn readback_image(&mut self, image: ImageHandle) -> ReadbackTicket {
+		self.push_readback(ReadbackSource::Image(image))
+	}
+
+	pub fn readback_backbuffer(&mut self) -> ReadbackTicket {
+		self.push_readback(ReadbackSource::Backbuffer)
+	}
+
+	fn push_readback(&mut self, source: ReadbackSource) -> ReadbackTicket {
+		let ticket = ReadbackTicket(self.next_readback_ticket);
+		self.next_readback_ticket += 1;
+
+		self.readbacks.push(ReadbackRequest { ticket, source });
+		ticket
+	}
+
 	// TODO(pat.m): maybe reserved buffers shouldn't use the upload heap?
 	// upload heap is mapped, host visible, and that might not be ideal for GPU-only visible stuff
 	pub fn reserve_buffer(&mut self, size: usize) -> BufferHandle {
@@ -142,7 +209,12 @@ impl FrameState {
 	}
 
 	pub fn draw(&mut self, pass: PassHandle, vertex_shader: ShaderHandle, fragment_shader: ShaderHandle) -> DrawCmdBuilder<'_> {
-		DrawCmdBuilder::new(self, pass, vertex_shader, fragment_shader)
+		DrawCmdBuilder::new(self, pass, vertex_shader, Some(fragment_shader))
+	}
+
+	pub fn draw_program(&mut self, pass: PassHandle, program: ProgramHandle) -> DrawCmdBuilder<'_> {
+		let vertex_shader = program.vertex.expect("Trying to draw with program that has no vertex stage");
+		DrawCmdBuilder::new(self, pass, vertex_shader, program.fragment)
 	}
 
 	pub fn dispatch(&mut self, pass: PassHandle, compute_shader: ShaderHandle) -> DispatchCmdBuilder<'_> {
@@ -333,9 +405,26 @@ pub enum ImageBinding {
 		handle: ImageHandle,
 		location: ImageBindingLocation,
 		read_write: bool,
+
+		/// The mip level to read or write.
+		level: u32,
+		layers: ImageLayers,
 	}
 }
 
+/// Which layers of an array, cube or 3D image are bound to an image unit.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ImageLayers {
+	/// Every layer if the shader declares an array, cube or 3D image, otherwise the first.
+	Auto,
+
+	/// A single array layer, cubemap face or 3D slice. Faces of cube arrays are addressed as `cube * 6 + face`.
+	Single(u32),
+
+	/// Every layer, regardless of what the shader declares.
+	All,
+}
+
 
 impl ImageBinding {
 	pub fn texture(handle: ImageHandle, sampler: SamplerDef, location: impl Into<ImageBindingLocation>) -> Self {
@@ -351,6 +440,8 @@ impl ImageBinding {
 			handle,
 			location: location.into(),
 			read_write: false,
+			level: 0,
+			layers: ImageLayers::Auto,
 		}
 	}
 
@@ -359,14 +450,50 @@ impl ImageBinding {
 			handle,
 			location: location.into(),
 			read_write: true,
+			level: 0,
+			layers: ImageLayers::Auto,
 		}
 	}
 
+	/// Restricts an image binding to a single layer or face. Has no effect on texture bindings.
+	pub fn with_layer(self, layer: u32) -> Self {
+		self.with_layers(ImageLayers::Single(layer))
+	}
+
+	/// Binds every layer of an image, even if the shader doesn't declare an array. Has no effect on texture bindings.
+	pub fn layered(self) -> Self {
+		self.with_layers(ImageLayers::All)
+	}
+
+	pub fn with_layers(mut self, new_layers: ImageLayers) -> Self {
+		if let ImageBinding::Image{ layers, .. } = &mut self {
+			*layers = new_layers;
+		}
+
+		self
+	}
+
+	/// Selects the mip level of an image binding. Has no effect on texture bindings.
+	pub fn with_level(mut self, new_level: u32) -> Self {
+		if let ImageBinding::Image{ level, .. } = &mut self {
+			*level = new_level;
+		}
+
+		self
+	}
+
 	pub fn image_handle(&self) -> ImageHandle {
 		let (ImageBinding::Texture{handle, ..} | ImageBinding::Image{handle, ..}) = self;
 		*handle
 	}
 
+	pub fn kind(&self) -> ImageBindingKind {
+		match self {
+			ImageBinding::Texture{..} => ImageBindingKind::Sampler,
+			ImageBinding::Image{..} => ImageBindingKind::Image,
+		}
+	}
+
 	pub fn location(&self) -> ImageBindingLocation {
 		let (ImageBinding::Texture{location, ..} | ImageBinding::Image{location, ..}) = self;
 		*location
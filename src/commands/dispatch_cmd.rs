@@ -20,6 +20,9 @@ pub struct DispatchCmd {
 
 	pub block_bindings: Vec<(BlockBinding, BufferHandle)>,
 	pub image_bindings: Vec<ImageBinding>,
+
+	// Feature keys used to select shader variants
+	pub features: Vec<&'static str>,
 }
 
 
@@ -76,6 +79,48 @@ impl<'fs> DispatchCmdBuilder<'fs> {
 		self.cmd.image_bindings.push(ImageBinding::image_rw(image, location));
 		self
 	}
+
+	pub fn image_layer(&mut self, location: impl Into<ImageBindingLocation>, image: ImageHandle, layer: u32) -> &mut Self {
+		self.cmd.image_bindings.push(ImageBinding::image(image, location).with_layer(layer));
+		self
+	}
+
+	pub fn image_rw_layer(&mut self, location: impl Into<ImageBindingLocation>, image: ImageHandle, layer: u32) -> &mut Self {
+		self.cmd.image_bindings.push(ImageBinding::image_rw(image, location).with_layer(layer));
+		self
+	}
+
+	pub fn image_level(&mut self, location: impl Into<ImageBindingLocation>, image: ImageHandle, level: u32) -> &mut Self {
+		self.cmd.image_bindings.push(ImageBinding::image(image, location).with_level(level));
+		self
+	}
+
+	pub fn image_rw_level(&mut self, location: impl Into<ImageBindingLocation>, image: ImageHandle, level: u32) -> &mut Self {
+		self.cmd.image_bindings.push(ImageBinding::image_rw(image, location).with_level(level));
+		self
+	}
+
+	/// Binds every layer of `image`, even if the shader doesn't declare an array, cube or 3D image.
+	pub fn image_layered(&mut self, location: impl Into<ImageBindingLocation>, image: ImageHandle) -> &mut Self {
+		self.cmd.image_bindings.push(ImageBinding::image(image, location).layered());
+		self
+	}
+
+	pub fn image_rw_layered(&mut self, location: impl Into<ImageBindingLocation>, image: ImageHandle) -> &mut Self {
+		self.cmd.image_bindings.push(ImageBinding::image_rw(image, location).layered());
+		self
+	}
+
+	/// For combinations the other methods don't cover - e.g., a single mip level of a single cubemap face.
+	pub fn image_binding(&mut self, binding: ImageBinding) -> &mut Self {
+		self.cmd.image_bindings.push(binding);
+		self
+	}
+
+	pub fn feature(&mut self, key: &'static str) -> &mut Self {
+		self.cmd.features.push(key);
+		self
+	}
 }
 
 
@@ -88,6 +133,7 @@ impl<'fs> DispatchCmdBuilder<'fs> {
 				num_groups: DispatchSizeSource::Explicit(Vec3i::splat(1)),
 				block_bindings: Vec::new(),
 				image_bindings: Vec::new(),
+				features: Vec::new(),
 			}),
 			pass,
 		}
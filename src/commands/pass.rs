@@ -1,5 +1,5 @@
 use super::{FrameState, Command};
-use crate::resource_manager::{FboDef, ImageHandle};
+use crate::resource_manager::{FboDef, FboAttachment, ImageHandle};
 
 
 #[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
@@ -11,9 +11,46 @@ pub struct Pass {
 	pub name: String,
 	pub commands: Vec<Command>,
 	pub fbo_def: FboDef,
+	pub resolves: Vec<AttachmentResolve>,
 	pub wants_timer_query: bool,
 }
 
+/// Copies a multisampled attachment into a single sampled image once a pass has finished.
+#[derive(Debug, Copy, Clone)]
+pub struct AttachmentResolve {
+	pub source: ResolveSource,
+	pub destination: FboAttachment,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ResolveSource {
+	Color(u32),
+
+	/// Whichever of the depth or depth/stencil attachments is set. Stencil isn't resolved.
+	Depth,
+}
+
+impl AttachmentResolve {
+	/// The framebuffer `destination` is blitted into.
+	pub fn destination_fbo_def(&self) -> FboDef {
+		let mut def = FboDef::default();
+
+		match self.source {
+			ResolveSource::Color(_) => def.set_color_attachment(0, self.destination),
+			ResolveSource::Depth => def.depth_attachment = Some(self.destination),
+		}
+
+		def
+	}
+
+	pub fn source_attachment(&self, fbo_def: &FboDef) -> Option<FboAttachment> {
+		match self.source {
+			ResolveSource::Color(point) => fbo_def.color_attachments.get(point as usize).copied().flatten(),
+			ResolveSource::Depth => fbo_def.depth_attachment.or(fbo_def.depth_stencil_attachment),
+		}
+	}
+}
+
 #[must_use]
 pub struct PassBuilder<'fs> {
 	pass: &'fs mut Pass,
@@ -28,6 +65,7 @@ impl<'fs> PassBuilder<'fs> {
 			name,
 			commands: Vec::new(),
 			fbo_def: FboDef::default(),
+			resolves: Vec::new(),
 			wants_timer_query: false,
 		});
 
@@ -37,24 +75,56 @@ impl<'fs> PassBuilder<'fs> {
 		}
 	}
 
-	pub fn color_attachment(&mut self, attachment_point: u32, image: ImageHandle) -> &mut Self {
-		assert!(attachment_point < 4);
+	/// Attachment points are only limited by GL_MAX_COLOR_ATTACHMENTS, which is checked when the framebuffer is created.
+	/// Takes an `ImageHandle`, or an `FboAttachment` to pick a mip level or layer.
+	pub fn color_attachment(&mut self, attachment_point: u32, attachment: impl Into<FboAttachment>) -> &mut Self {
+		self.pass.fbo_def.set_color_attachment(attachment_point, attachment.into());
+		self
+	}
+
+	/// Attach a single layer or cubemap face of `image`.
+	pub fn color_attachment_layer(&mut self, attachment_point: u32, image: ImageHandle, layer: u32) -> &mut Self {
+		self.color_attachment(attachment_point, FboAttachment::from(image).layer(layer))
+	}
+
+	pub fn depth_attachment(&mut self, attachment: impl Into<FboAttachment>) -> &mut Self {
+		self.pass.fbo_def.depth_attachment = Some(attachment.into());
+		self
+	}
 
-		let def = &mut self.pass.fbo_def;
+	pub fn stencil_attachment(&mut self, attachment: impl Into<FboAttachment>) -> &mut Self {
+		self.pass.fbo_def.stencil_attachment = Some(attachment.into());
+		self
+	}
 
-		match attachment_point {
-			0 => def.color_attachment_0 = Some(image),
-			1 => def.color_attachment_1 = Some(image),
-			2 => def.color_attachment_2 = Some(image),
-			3 => def.color_attachment_3 = Some(image),
-			_ => unreachable!(),
-		}
+	pub fn depth_stencil_attachment(&mut self, attachment: impl Into<FboAttachment>) -> &mut Self {
+		self.pass.fbo_def.depth_stencil_attachment = Some(attachment.into());
+		self
+	}
+
+	/// Attach a single layer or cubemap face of `image` - e.g., for rendering shadow cubemaps a face at a time.
+	pub fn depth_stencil_attachment_layer(&mut self, image: ImageHandle, layer: u32) -> &mut Self {
+		self.depth_stencil_attachment(FboAttachment::from(image).layer(layer))
+	}
+
+	/// Resolves the multisampled colour attachment at `attachment_point` into `destination` at the end of the pass.
+	pub fn resolve_color_attachment(&mut self, attachment_point: u32, destination: impl Into<FboAttachment>) -> &mut Self {
+		self.pass.resolves.push(AttachmentResolve {
+			source: ResolveSource::Color(attachment_point),
+			destination: destination.into(),
+		});
 
 		self
 	}
 
-	pub fn depth_stencil_attachment(&mut self, image: ImageHandle) -> &mut Self {
-		self.pass.fbo_def.depth_stencil_attachment = Some(image);
+	/// Resolves the multisampled depth or depth/stencil attachment into `destination` at the end of the pass.
+	/// Each pixel gets the depth of a single sample, rather than an average.
+	pub fn resolve_depth_attachment(&mut self, destination: impl Into<FboAttachment>) -> &mut Self {
+		self.pass.resolves.push(AttachmentResolve {
+			source: ResolveSource::Depth,
+			destination: destination.into(),
+		});
+
 		self
 	}
 
@@ -1,6 +1,7 @@
 use crate::resource_manager::*;
 use crate::commands::{self, Command, FrameState, BufferHandle};
 use crate::upload_heap::UploadHeap;
+use crate::readback::{ReadbackQueue, ReadbackTicket, ReadbackImage};
 use common::math::{Vec2i, Vec3i};
 
 
@@ -12,6 +13,7 @@ pub const SSBO_ALIGNMENT: usize = 32;
 pub struct Context {
 	pub resource_manager: ResourceManager,
 	pub upload_heap: UploadHeap,
+	readback_queue: ReadbackQueue,
 
 	vao_name: u32,
 
@@ -43,6 +45,7 @@ impl Context {
 		Ok(Self{
 			resource_manager,
 			upload_heap,
+			readback_queue: ReadbackQueue::new(),
 
 			vao_name,
 
@@ -55,28 +58,90 @@ impl Context {
 
 	pub fn start_frame(&mut self) {
 		self.upload_heap.reset();
+		self.resource_manager.process_async_image_loads();
+		self.resource_manager.process_pending_destruction();
+		self.resource_manager.reload_changed_shaders();
 	}
 
-	pub fn end_frame(&mut self, frame_state: &mut FrameState) {
+	/// Frames that fail validation are dropped without executing any passes. Image uploads and readbacks still happen,
+	/// so readback tickets from the frame still resolve - to the image as it was before the frame's passes.
+	/// Invalid uploads and readbacks are skipped, and their tickets are treated as unknown by `wait_readback`.
+	pub fn end_frame(&mut self, frame_state: &mut FrameState) -> Result<(), GfxError> {
+		let result = self.execute_frame(frame_state);
+
+		if result.is_err() {
+			self.execute_dropped_frame(frame_state);
+		}
+
+		self.upload_heap.notify_finished();
+		frame_state.reset();
+
+		self.process_queries();
+
+		result
+	}
+
+	fn execute_frame(&mut self, frame_state: &mut FrameState) -> Result<(), GfxError> {
 		use crate::commands::{BlockBinding, DispatchSizeSource, ImageBindingLocation};
 
 		// TODO(pat.m): non-ubo data could be interleaved with ubo data to save space
 
 		// let mut commands = std::mem::replace(&mut frame_state.commands, Vec::new());
 
-		let FrameState { passes, allocator } = frame_state;
+		let FrameState { passes, allocator, image_uploads, readbacks, .. } = frame_state;
 
 
-		// Resolve named buffer block bindings
+		// Resolve shader variants and named buffer block bindings
 		for cmd in passes.iter_mut().flat_map(|pass| pass.commands.iter_mut()) {
+			let features = std::mem::take(cmd.shader_features_mut());
+			if !features.is_empty() {
+				for shader in cmd.shaders_mut() {
+					*shader = self.resource_manager.get_shader_variant(*shader, features.iter().copied())
+						.map_err(|error| GfxError::ShaderVariantFailed {
+							shader: *shader,
+							features: features.iter().map(|feature| feature.to_string()).collect(),
+							reason: format!("{error:#}"),
+						})?;
+				}
+			}
+
+			// Collected up front since the pipeline borrows the resource manager
+			let mut image_properties = Vec::new();
+
+			if let Some(image_bindings) = cmd.image_bindings() {
+				use crate::commands::{ImageBinding, ImageLayers};
+
+				for binding in image_bindings {
+					let handle = binding.image_handle();
+					let image = self.resource_manager.resolve_image(handle)
+						.ok_or(GfxError::MissingImage(handle))?;
+
+					if let &ImageBinding::Image{level, layers, ..} = binding {
+						if level >= image.levels {
+							return Err(GfxError::InvalidImageBindingLevel { image: handle, level, levels: image.levels });
+						}
+
+						let addressable_layers = image.addressable_layers(level);
+
+						if let ImageLayers::Single(layer) = layers
+							&& layer >= addressable_layers
+						{
+							return Err(GfxError::InvalidImageBindingLayer { image: handle, layer, layers: addressable_layers });
+						}
+					}
+
+					image_properties.push((image.samples, image.format));
+				}
+			}
+
 			let Some(pipeline_def) = cmd.pipeline_def() else { continue };
-			let pipeline = self.resource_manager.get_pipeline(&pipeline_def).unwrap();
+			let pipeline = self.resource_manager.get_pipeline(&pipeline_def)?;
 
 			if let Some(block_bindings) = cmd.block_bindings_mut() {
 				for (binding, _) in block_bindings {
 					if let BlockBinding::Named(name) = binding {
 						let block = pipeline.block_by_name(*name)
-							.expect(&format!("Couldn't find block binding with name '{name}'"));
+							.ok_or_else(|| GfxError::UnknownBlockBinding { name: name.to_string() })?;
 						*binding = BlockBinding::Explicit(block.binding_location);
 					}
 				}
@@ -85,10 +150,34 @@ impl Context {
 			if let Some(image_bindings) = cmd.image_bindings_mut() {
 				for binding in image_bindings.iter_mut() {
 					let ImageBindingLocation::Named(name) = binding.location() else { continue };
-					let unit = pipeline.image_binding_by_name(name)
-						.expect(&format!("Couldn't find image binding with name '{name}'"));
+					let description = pipeline.image_binding_by_name(name)
+						.ok_or_else(|| GfxError::UnknownImageBinding { name: name.to_string() })?;
+
+					if description.kind != binding.kind() {
+						return Err(GfxError::MismatchedImageBindingKind {
+							name: name.to_string(),
+							expected: description.kind,
+							found: binding.kind(),
+						});
+					}
+
+					binding.set_location(ImageBindingLocation::Explicit(description.unit));
+				}
+
+				for (binding, &(samples, image_format)) in image_bindings.iter().zip(&image_properties) {
+					let ImageBindingLocation::Explicit(unit) = binding.location() else { continue };
+					let Some(description) = pipeline.image_binding_by_unit(binding.kind(), unit) else { continue };
 
-					binding.set_location(ImageBindingLocation::Explicit(unit));
+					if description.dimensionality.is_multisampled() != (samples > 1) {
+						return Err(GfxError::MultisampleMismatch { unit, image_samples: samples });
+					}
+
+					// Images are bound using the format the shader expects, which must be compatible with the image
+					if let Some(unit_format) = description.format
+						&& !shader::image_formats_compatible(image_format, unit_format)
+					{
+						return Err(GfxError::IncompatibleImageFormat { unit, image_format, unit_format });
+					}
 				}
 			}
 		}
@@ -121,10 +210,61 @@ impl Context {
 
 
 
+		// Create framebuffers and validate uploads up front, so that nothing has been submitted if any are invalid
+		for pass in passes.iter() {
+			let source_size = self.resource_manager.get_fbo(&pass.fbo_def)?.viewport_size;
+
+			for resolve in pass.resolves.iter() {
+				use crate::commands::ResolveSource;
+
+				let Some(source) = resolve.source_attachment(&pass.fbo_def) else {
+					return Err(GfxError::MissingResolveSource { pass: pass.name.clone() });
+				};
+
+				if resolve.source == ResolveSource::Depth {
+					let format_of = |handle| self.resource_manager.resolve_image(handle)
+						.map(|image| image.format)
+						.ok_or(GfxError::MissingImage(handle));
+
+					let source_format = format_of(source.image)?;
+					let destination_format = format_of(resolve.destination.image)?;
+
+					if source_format != destination_format {
+						return Err(GfxError::MismatchedResolveFormat {
+							pass: pass.name.clone(),
+							source_format,
+							destination_format,
+						});
+					}
+				}
+
+				let destination_size = self.resource_manager.get_fbo(&resolve.destination_fbo_def())?.viewport_size;
+				if destination_size != source_size {
+					return Err(GfxError::MismatchedResolveSize {
+						pass: pass.name.clone(),
+						source: source_size,
+						destination: destination_size,
+					});
+				}
+			}
+		}
+
+		for upload in image_uploads.iter() {
+			self.validate_image_upload(upload)?;
+		}
+
+		for readback in readbacks.iter() {
+			crate::readback::validate_source(&self.resource_manager, readback.source)?;
+		}
+
 		allocator.upload_buffers(&mut self.upload_heap);
 
 		let upload_buffer_name = self.upload_heap.buffer_name();
 
+		if !image_uploads.is_empty() {
+			self.upload_images(image_uploads, allocator, upload_buffer_name);
+		}
+
 		let mut barrier_tracker = ResourceBarrierTracker::new();
 
 		for pass in passes.iter() {
@@ -133,17 +273,21 @@ impl Context {
 				gl::PushDebugGroup(gl::DEBUG_SOURCE_APPLICATION, 0, msg.len() as i32, msg.as_ptr() as *const _);
 			}
 
-			let fbo = self.resource_manager.get_fbo(&pass.fbo_def).unwrap();
+			let fbo = self.resource_manager.get_fbo(&pass.fbo_def)
+				.expect("Framebuffers should have been created before executing passes");
+
+			let fbo_name = fbo.name;
+			let fbo_size = fbo.viewport_size;
 
 			// TODO(pat.m): insert barriers for any dirty images used as fbo attachments
 			unsafe {
-				gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, fbo.name);
+				gl::BindFramebuffer(gl::DRAW_FRAMEBUFFER, fbo_name);
 
-				let Vec2i{x, y} = fbo.viewport_size;
+				let Vec2i{x, y} = fbo_size;
 				gl::Viewport(0, 0, x, y);
 
 				// TODO(pat.m): should be option! some passes may want to preserve contents!
-				if fbo.name != 0 {
+				if fbo_name != 0 {
 					gl::Clear(gl::COLOR_BUFFER_BIT|gl::DEPTH_BUFFER_BIT|gl::STENCIL_BUFFER_BIT);
 				}
 			}
@@ -176,7 +320,8 @@ impl Context {
 
 				// Lookup and bind pipeline
 				let pipeline = cmd.pipeline_def()
-					.map(|def| self.resource_manager.get_pipeline(&def).unwrap());
+					.map(|def| self.resource_manager.get_pipeline(&def)
+						.expect("Pipelines should have been created while resolving bindings"));
 
 				if let Some(pipeline) = pipeline {
 					unsafe {
@@ -215,15 +360,25 @@ impl Context {
 
 					// Bind textures and images
 					if let Some(bindings) = cmd.image_bindings() {
-						use crate::commands::ImageBinding;
-
-						for binding in bindings {
+						use crate::commands::{ImageBinding, ImageLayers};
+
+						// Collected up front since get_sampler needs the resource manager mutably
+						let unit_descriptions: Vec<_> = bindings.iter()
+							.map(|binding| match binding {
+								ImageBinding::Image{location: ImageBindingLocation::Explicit(unit), ..}
+									=> pipeline.image_binding_by_unit(ImageBindingKind::Image, *unit).copied(),
+								_ => None,
+							})
+							.collect();
+
+						for (binding, unit_description) in bindings.iter().zip(unit_descriptions) {
 							let image_handle = binding.image_handle();
 							let image = self.resource_manager.resolve_image(image_handle)
 								.expect("Failed to resolve image handle - probably use after delete");
 
 							let image_name = image.name;
 							let image_format = image.format;
+							let image_is_layered = image.texture_type.is_layered();
 
 							match binding {
 								ImageBinding::Texture{sampler, location: ImageBindingLocation::Explicit(unit), ..} => {
@@ -237,21 +392,34 @@ impl Context {
 									}
 								}
 
-								ImageBinding::Image{read_write, location: ImageBindingLocation::Explicit(unit), ..} => {
+								ImageBinding::Image{read_write, level, layers, location: ImageBindingLocation::Explicit(unit), ..} => {
 									barrier_tracker.insert_barrier(image_handle, gl::SHADER_IMAGE_ACCESS_BARRIER_BIT);
 
 									if *read_write {
 										barrier_tracker.mark_resource(image_handle);
 									}
 
-									let (level, layered, layer) = (0, 0, 0);
+									// Bind using the format the shader expects - compatibility was checked while resolving bindings
+									let unit_format = unit_description.and_then(|description| description.format)
+										.unwrap_or(image_format);
+
+									// Bind every layer if the shader expects them, unless told otherwise
+									let shader_is_layered = unit_description
+										.map_or(image_is_layered, |description| description.dimensionality.is_layered());
+
+									let (layered, layer) = match layers {
+										ImageLayers::Auto => (shader_is_layered as u8, 0),
+										ImageLayers::Single(layer) => (gl::FALSE, *layer as i32),
+										ImageLayers::All => (gl::TRUE, 0),
+									};
+
 									let access_flags = match read_write {
 										true => gl::READ_WRITE,
 										false => gl::READ_ONLY,
 									};
 
 									unsafe {
-										gl::BindImageTexture(*unit, image_name, level, layered, layer, access_flags, image_format);
+										gl::BindImageTexture(*unit, image_name, *level as i32, layered, layer, access_flags, unit_format);
 									}
 								}
 
@@ -305,6 +473,36 @@ impl Context {
 				}
 			}
 
+			for resolve in pass.resolves.iter() {
+				use crate::commands::ResolveSource;
+
+				let destination_fbo_name = self.resource_manager.get_fbo(&resolve.destination_fbo_def())
+					.expect("Framebuffers should have been created before executing passes")
+					.name;
+
+				let mask = match resolve.source {
+					ResolveSource::Color(_) => gl::COLOR_BUFFER_BIT,
+					ResolveSource::Depth => gl::DEPTH_BUFFER_BIT,
+				};
+
+				let Vec2i{x, y} = fbo_size;
+
+				unsafe {
+					if let ResolveSource::Color(attachment_point) = resolve.source {
+						gl::NamedFramebufferReadBuffer(fbo_name, gl::COLOR_ATTACHMENT0 + attachment_point);
+					}
+
+					// Multisample resolves must be unscaled, and depth can only be blitted with NEAREST
+					gl::BlitNamedFramebuffer(fbo_name, destination_fbo_name, 0, 0, x, y, 0, 0, x, y, mask, gl::NEAREST);
+				}
+			}
+
+			if pass.resolves.iter().any(|resolve| matches!(resolve.source, crate::commands::ResolveSource::Color(_))) {
+				unsafe {
+					gl::NamedFramebufferReadBuffer(fbo_name, pass.fbo_def.default_read_buffer());
+				}
+			}
+
 			if let Some(query) = timer_query {
 				unsafe {
 					gl::EndQuery(gl::TIME_ELAPSED);
@@ -318,10 +516,123 @@ impl Context {
 			}
 		}
 
-		self.upload_heap.notify_finished();
-		frame_state.reset();
+		self.readback_queue.start(&self.resource_manager, readbacks);
 
-		self.process_queries();
+		Ok(())
+	}
+
+
+	/// Validation happens before anything is submitted, so none of the frame's buffers have been uploaded yet.
+	fn execute_dropped_frame(&mut self, frame_state: &mut FrameState) {
+		let FrameState { allocator, image_uploads, readbacks, .. } = frame_state;
+
+		image_uploads.retain(|upload| self.validate_image_upload(upload).is_ok());
+		readbacks.retain(|readback| crate::readback::validate_source(&self.resource_manager, readback.source).is_ok());
+
+		allocator.upload_buffers(&mut self.upload_heap);
+
+		if !image_uploads.is_empty() {
+			let upload_buffer_name = self.upload_heap.buffer_name();
+			self.upload_images(image_uploads, allocator, upload_buffer_name);
+		}
+
+		self.readback_queue.start(&self.resource_manager, readbacks);
+	}
+
+
+	/// Returns None until the readback has completed. Each ticket can only be collected once.
+	pub fn poll_readback(&mut self, ticket: ReadbackTicket) -> Option<ReadbackImage> {
+		self.readback_queue.poll(ticket)
+	}
+
+	/// Blocks until the readback has completed. Returns None if the ticket is unknown or was already collected.
+	pub fn wait_readback(&mut self, ticket: ReadbackTicket) -> Option<ReadbackImage> {
+		self.readback_queue.wait(ticket)
+	}
+
+
+	fn validate_image_upload(&self, upload: &commands::ImageUpload) -> Result<(), GfxError> {
+		let image = self.resource_manager.resolve_image(upload.image)
+			.ok_or(GfxError::MissingImage(upload.image))?;
+
+		let Some((_, _, texel_size)) = pixel_transfer_format(image.format) else {
+			return Err(GfxError::UnsupportedUploadFormat { image: upload.image, format: image.format });
+		};
+
+		let ImageRegion{offset, size, level, layer} = upload.region;
+
+		let level_size = Vec2i::new(
+			(image.resolved_size.x >> level.min(31)).max(1),
+			(image.resolved_size.y >> level.min(31)).max(1),
+		);
+
+		let in_bounds = level < image.levels
+			&& layer < image.addressable_layers(level)
+			&& offset.x >= 0 && offset.y >= 0
+			&& size.x >= 0 && size.y >= 0
+			&& offset.x as i64 + size.x as i64 <= level_size.x as i64
+			&& offset.y as i64 + size.y as i64 <= level_size.y as i64;
+
+		if !in_bounds {
+			return Err(GfxError::UploadOutOfBounds { image: upload.image, region: upload.region });
+		}
+
+		let expected = size.x as usize * size.y as usize * texel_size;
+		if upload.byte_size != expected {
+			return Err(GfxError::MismatchedUploadSize { image: upload.image, expected, found: upload.byte_size });
+		}
+
+		Ok(())
+	}
+
+	fn upload_images(&mut self, uploads: &[commands::ImageUpload], allocator: &commands::TransientAllocator, upload_buffer_name: u32) {
+		use crate::upload_heap::BufferAllocation;
+
+		unsafe {
+			// Shader image stores from previous frames must land before texels are overwritten.
+			// Passes are executed after this so later reads see the new data without further barriers.
+			gl::MemoryBarrier(gl::TEXTURE_UPDATE_BARRIER_BIT | gl::PIXEL_BUFFER_BARRIER_BIT);
+
+			gl::BindBuffer(gl::PIXEL_UNPACK_BUFFER, upload_buffer_name);
+			gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+		}
+
+		for upload in uploads {
+			let image = self.resource_manager.resolve_image(upload.image)
+				.expect("Uploads should have been validated before executing");
+
+			let (upload_format, upload_type, _) = pixel_transfer_format(image.format)
+				.expect("Uploads should have been validated before executing");
+
+			let ImageRegion{offset, size, level, layer} = upload.region;
+			let BufferAllocation{offset: buffer_offset, ..} = allocator.resolve_buffer_allocation(upload.buffer);
+
+			// With a bound unpack buffer the data pointer is an offset into it
+			let data_offset = buffer_offset as *const _;
+
+			unsafe {
+				match image.texture_type {
+					TextureType::Tex2D => gl::TextureSubImage2D(image.name, level as i32, offset.x, offset.y,
+						size.x, size.y,
+						upload_format,
+						upload_type,
+						data_offset),
+
+					_ => gl::TextureSubImage3D(image.name, level as i32, offset.x, offset.y, layer as i32,
+						size.x, size.y, 1,
+						upload_format,
+						upload_type,
+						data_offset),
+				}
+			}
+		}
+
+		unsafe {
+			gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+
+			// Loaders upload from client memory, which only works with no unpack buffer bound
+			gl::BindBuffer(gl::PIXEL_UNPACK_BUFFER, 0);
+		}
 	}
 
 
@@ -352,6 +663,25 @@ impl Context {
 
 
 
+impl Drop for Context {
+	fn drop(&mut self) {
+		// NOTE: resource_manager, upload_heap and readback_queue clean up after themselves once this returns
+		unsafe {
+			for query in self.in_flight_queries.drain(..) {
+				gl::DeleteQueries(1, &query.gl_name);
+			}
+
+			gl::DeleteQueries(self.query_pool.len() as i32, self.query_pool.as_ptr());
+			self.query_pool.clear();
+
+			gl::BindVertexArray(0);
+			gl::DeleteVertexArrays(1, &self.vao_name);
+		}
+	}
+}
+
+
+
 use std::collections::HashMap;
 
 #[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
@@ -5,6 +5,7 @@ mod resource_manager;
 mod commands;
 mod context;
 mod upload_heap;
+mod readback;
 
 use common::math::*;
 use resource_manager::*;
@@ -28,9 +29,9 @@ struct Game {
 
 	vert_shader: ShaderHandle,
 	vert_indexed_shader: ShaderHandle,
-	vert_sprite_shader: ShaderHandle,
 	frag_shader: ShaderHandle,
-	frag_textured_shader: ShaderHandle,
+
+	sprite_program: ProgramHandle,
 
 	gen_args_compute_shader: ShaderHandle,
 	gen_color_compute_shader: ShaderHandle,
@@ -54,27 +55,37 @@ impl Game {
 		let mut context = Context::new()?;
 		let frame_state = FrameState::new();
 
-		let vert_shader = context.resource_manager.load_shader(&ShaderDef::vertex("shaders/test.vert.glsl"))?;
-		let vert_indexed_shader = context.resource_manager.load_shader(&ShaderDef::vertex("shaders/test_indexed.vert.glsl"))?;
-		let vert_sprite_shader = context.resource_manager.load_shader(&ShaderDef::vertex("shaders/sprite.vert.glsl"))?;
+		context.resource_manager.set_hot_reload_enabled(cfg!(debug_assertions));
+
+		use anyhow::Context as _;
+
+		context.resource_manager.load_manifest("manifest.ron")?;
+
+		let resource_manager = &context.resource_manager;
+		let shader = |name: &str| resource_manager.shader_by_name(name).with_context(|| format!("Missing shader '{name}'"));
+		let image = |name: &str| resource_manager.image_by_name(name).with_context(|| format!("Missing image '{name}'"));
 
-		let frag_shader = context.resource_manager.load_shader(&ShaderDef::fragment("shaders/test.frag.glsl"))?;
-		let frag_textured_shader = context.resource_manager.load_shader(&ShaderDef::fragment("shaders/textured.frag.glsl"))?;
+		let vert_shader = shader("test_vert")?;
+		let vert_indexed_shader = shader("test_indexed_vert")?;
 
-		let gen_args_compute_shader = context.resource_manager.load_shader(&ShaderDef::compute("shaders/gen_args.cs.glsl"))?;
-		let gen_color_compute_shader = context.resource_manager.load_shader(&ShaderDef::compute("shaders/gen_color.cs.glsl"))?;
-		let post_process_compute_shader = context.resource_manager.load_shader(&ShaderDef::compute("shaders/post_process.cs.glsl"))?;
+		let frag_shader = shader("test_frag")?;
 
-		let rgb_to_yuv_cs = context.resource_manager.load_shader(&ShaderDef::compute("shaders/rgb_to_yuv.cs.glsl"))?;
-		let yuv_to_rgb_cs = context.resource_manager.load_shader(&ShaderDef::compute("shaders/yuv_to_rgb.cs.glsl"))?;
-		let blur_uv_cs = context.resource_manager.load_shader(&ShaderDef::compute("shaders/blur_uv.cs.glsl"))?;
+		let sprite_program = resource_manager.program_by_name("sprite").context("Missing program 'sprite'")?;
 
-		let coolcat_image = context.resource_manager.load_image(&ImageDef::new("images/coolcat.png"))?;
+		let gen_args_compute_shader = shader("gen_args")?;
+		let gen_color_compute_shader = shader("gen_color")?;
+		let post_process_compute_shader = shader("post_process")?;
 
-		let render_target = context.resource_manager.load_image(&ImageDef::render_target(gl::R11F_G11F_B10F))?;
-		let yuv_target = context.resource_manager.load_image(&ImageDef::render_target(gl::RGBA16F))?;
-		let yuv2_target = context.resource_manager.load_image(&ImageDef::render_target(gl::RGBA16F))?;
-		let depth_stencil_image = context.resource_manager.load_image(&ImageDef::depth_stencil())?;
+		let rgb_to_yuv_cs = shader("rgb_to_yuv")?;
+		let yuv_to_rgb_cs = shader("yuv_to_rgb")?;
+		let blur_uv_cs = shader("blur_uv")?;
+
+		let coolcat_image = image("coolcat")?;
+
+		let render_target = image("render_target")?;
+		let yuv_target = image("yuv_target")?;
+		let yuv2_target = image("yuv2_target")?;
+		let depth_stencil_image = image("depth_stencil")?;
 
 		unsafe {
 			gl::Enable(gl::DEPTH_TEST);
@@ -87,10 +98,10 @@ impl Game {
 
 			vert_shader,
 			vert_indexed_shader,
-			vert_sprite_shader,
 
 			frag_shader,
-			frag_textured_shader,
+
+			sprite_program,
 
 			gen_args_compute_shader,
 			gen_color_compute_shader,
@@ -211,7 +222,7 @@ impl main_loop::MainLoop for Game {
 				color: [1.0, 1.0, 1.0, 1.0],
 			};
 
-			self.frame_state.draw(draw_pass, self.vert_sprite_shader, self.frag_textured_shader)
+			self.frame_state.draw_program(draw_pass, self.sprite_program)
 				.elements(6)
 				.ubo(0, proj_view_buffer)
 				.buffer("SpriteData", &sprite_data)
@@ -260,13 +271,15 @@ impl main_loop::MainLoop for Game {
 
 
 		// Present
-		self.frame_state.draw(final_draw_pass, self.vert_sprite_shader, self.frag_textured_shader)
+		self.frame_state.draw_program(final_draw_pass, self.sprite_program)
 			.elements(6)
 			.ubo(0, &Mat4::identity())
 			.buffer("SpriteData", &[1.0f32; 4])
 			.texture("u_texture", self.render_target, SamplerDef::nearest_clamped());
 
-		self.context.end_frame(&mut self.frame_state);
+		if let Err(error) = self.context.end_frame(&mut self.frame_state) {
+			eprintln!("Frame dropped: {error}");
+		}
 	}
 
 	fn resize(&mut self, size: Vec2i) {
@@ -0,0 +1,290 @@
+use crate::resource_manager::{ResourceManager, ImageHandle, TextureType, GfxError, pixel_transfer_format};
+use common::math::Vec2i;
+use std::io::Write;
+
+
+#[derive(Copy, Clone, Debug, Hash, Eq, PartialEq)]
+pub struct ReadbackTicket(pub u64);
+
+#[derive(Copy, Clone, Debug)]
+pub enum ReadbackSource {
+	Image(ImageHandle),
+	Backbuffer,
+}
+
+#[derive(Debug)]
+pub struct ReadbackRequest {
+	pub ticket: ReadbackTicket,
+	pub source: ReadbackSource,
+}
+
+
+/// Tightly packed texels copied back from the GPU. Rows are bottom to top, as GL stores them.
+#[derive(Debug)]
+pub struct ReadbackImage {
+	pub size: Vec2i,
+
+	/// Pixel transfer format and type, e.g. (RGBA, UNSIGNED_BYTE).
+	/// Float formats are always read back as FLOAT so they can be saved without conversion.
+	pub format: u32,
+	pub ty: u32,
+
+	pub data: Vec<u8>,
+}
+
+impl ReadbackImage {
+	pub fn num_channels(&self) -> usize {
+		match self.format {
+			gl::RED | gl::RED_INTEGER | gl::DEPTH_COMPONENT => 1,
+			gl::RG | gl::RG_INTEGER => 2,
+			gl::RGB | gl::RGB_INTEGER => 3,
+			_ => 4,
+		}
+	}
+
+	/// Saves as PNG or PFM depending on the extension of `path`.
+	pub fn save(&self, path: impl AsRef<std::path::Path>) -> anyhow::Result<()> {
+		let path = path.as_ref();
+
+		match path.extension().and_then(|ext| ext.to_str()) {
+			Some("png") => self.save_png(path),
+			Some("pfm") => self.save_pfm(path),
+			_ => anyhow::bail!("Can't save readback to '{}' - only .png and .pfm are supported", path.display()),
+		}
+	}
+
+	/// Only supported for 8-bit formats.
+	pub fn save_png(&self, path: impl AsRef<std::path::Path>) -> anyhow::Result<()> {
+		use image::ColorType;
+
+		anyhow::ensure!(self.ty == gl::UNSIGNED_BYTE, "PNGs can only be saved from 8-bit images, use PFM for float data");
+
+		let color_type = match self.num_channels() {
+			1 => ColorType::L8,
+			2 => ColorType::La8,
+			3 => ColorType::Rgb8,
+			_ => ColorType::Rgba8,
+		};
+
+		// PNG rows are top to bottom
+		let row_size = self.size.x as usize * self.num_channels();
+		let flipped: Vec<u8> = self.data.chunks_exact(row_size).rev()
+			.flatten()
+			.copied()
+			.collect();
+
+		image::save_buffer(path, &flipped, self.size.x as u32, self.size.y as u32, color_type)?;
+		Ok(())
+	}
+
+	/// Only supported for float formats. Two channel images are padded with zero blue, and alpha is dropped.
+	/// https://www.pauldebevec.com/Research/HDR/PFM/
+	pub fn save_pfm(&self, path: impl AsRef<std::path::Path>) -> anyhow::Result<()> {
+		anyhow::ensure!(self.ty == gl::FLOAT, "PFMs can only be saved from float images, use PNG for 8-bit data");
+
+		let num_channels = self.num_channels();
+		let is_greyscale = num_channels == 1;
+
+		let mut file = std::io::BufWriter::new(std::fs::File::create(path)?);
+
+		// Negative scale means little endian. PFM rows are bottom to top like GL, so no flip needed
+		let magic = if is_greyscale { "Pf" } else { "PF" };
+		write!(file, "{magic}\n{} {}\n-1.0\n", self.size.x, self.size.y)?;
+
+		for texel in self.data.chunks_exact(num_channels * 4) {
+			let channel = |index: usize| match texel.get(index*4..index*4 + 4) {
+				Some(bytes) => f32::from_le_bytes(bytes.try_into().unwrap()),
+				None => 0.0,
+			};
+
+			let output_channels = if is_greyscale { 1 } else { 3 };
+
+			for index in 0..output_channels {
+				file.write_all(&channel(index).to_le_bytes())?;
+			}
+		}
+
+		file.flush()?;
+		Ok(())
+	}
+}
+
+
+#[derive(Debug)]
+struct PendingReadback {
+	ticket: ReadbackTicket,
+	buffer_name: u32,
+	fence: gl::types::GLsync,
+
+	size: Vec2i,
+	format: u32,
+	ty: u32,
+	byte_size: usize,
+}
+
+
+/// Copies images into pixel pack buffers at the end of a frame and hands them back once their fences signal.
+#[derive(Debug)]
+pub struct ReadbackQueue {
+	pending: Vec<PendingReadback>,
+}
+
+impl ReadbackQueue {
+	pub fn new() -> ReadbackQueue {
+		ReadbackQueue {
+			pending: Vec::new(),
+		}
+	}
+
+	pub fn start(&mut self, resource_manager: &ResourceManager, requests: &[ReadbackRequest]) {
+		if requests.is_empty() {
+			return
+		}
+
+		unsafe {
+			// Make sure image stores this frame are visible to the copies
+			gl::MemoryBarrier(gl::PIXEL_BUFFER_BARRIER_BIT | gl::TEXTURE_UPDATE_BARRIER_BIT | gl::FRAMEBUFFER_BARRIER_BIT);
+			gl::PixelStorei(gl::PACK_ALIGNMENT, 1);
+		}
+
+		for request in requests {
+			let (size, format, ty, texel_size) = readback_layout(resource_manager, request.source)
+				.expect("Readback sources should have been validated before starting");
+
+			let byte_size = size.x as usize * size.y as usize * texel_size;
+			let mut buffer_name = 0;
+
+			unsafe {
+				gl::CreateBuffers(1, &mut buffer_name);
+				gl::NamedBufferStorage(buffer_name, byte_size as isize, std::ptr::null(), gl::CLIENT_STORAGE_BIT);
+
+				gl::BindBuffer(gl::PIXEL_PACK_BUFFER, buffer_name);
+
+				// With a bound pack buffer the destination pointer is an offset into it
+				match request.source {
+					ReadbackSource::Image(handle) => {
+						let image_name = resource_manager.resolve_image(handle).unwrap().name;
+						gl::GetTextureImage(image_name, 0, format, ty, byte_size as i32, std::ptr::null_mut());
+					}
+
+					ReadbackSource::Backbuffer => {
+						gl::BindFramebuffer(gl::READ_FRAMEBUFFER, 0);
+						gl::ReadPixels(0, 0, size.x, size.y, format, ty, std::ptr::null_mut());
+					}
+				}
+			}
+
+			let fence = unsafe { gl::FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0) };
+
+			self.pending.push(PendingReadback {
+				ticket: request.ticket,
+				buffer_name,
+				fence,
+				size,
+				format,
+				ty,
+				byte_size,
+			});
+		}
+
+		unsafe {
+			gl::BindBuffer(gl::PIXEL_PACK_BUFFER, 0);
+			gl::PixelStorei(gl::PACK_ALIGNMENT, 4);
+		}
+	}
+
+	/// Returns None until the readback has completed. Each ticket can only be collected once.
+	pub fn poll(&mut self, ticket: ReadbackTicket) -> Option<ReadbackImage> {
+		let index = self.pending.iter().position(|pending| pending.ticket == ticket)?;
+
+		let result = unsafe { gl::ClientWaitSync(self.pending[index].fence, 0, 0) };
+		if result != gl::ALREADY_SIGNALED && result != gl::CONDITION_SATISFIED {
+			return None
+		}
+
+		Some(self.finish(index))
+	}
+
+	/// Blocks until the readback completes. Returns None if `ticket` is unknown or was already collected.
+	pub fn wait(&mut self, ticket: ReadbackTicket) -> Option<ReadbackImage> {
+		let index = self.pending.iter().position(|pending| pending.ticket == ticket)?;
+
+		// wait in blocks of 0.1ms
+		let timeout_ns = 100_000;
+
+		unsafe {
+			while let result = gl::ClientWaitSync(self.pending[index].fence, gl::SYNC_FLUSH_COMMANDS_BIT, timeout_ns)
+				&& result != gl::ALREADY_SIGNALED && result != gl::CONDITION_SATISFIED
+			{}
+		}
+
+		Some(self.finish(index))
+	}
+
+	fn finish(&mut self, index: usize) -> ReadbackImage {
+		let pending = self.pending.remove(index);
+		let mut data = vec![0u8; pending.byte_size];
+
+		unsafe {
+			gl::GetNamedBufferSubData(pending.buffer_name, 0, pending.byte_size as isize, data.as_mut_ptr() as *mut _);
+			gl::DeleteBuffers(1, &pending.buffer_name);
+			gl::DeleteSync(pending.fence);
+		}
+
+		ReadbackImage {
+			size: pending.size,
+			format: pending.format,
+			ty: pending.ty,
+			data,
+		}
+	}
+}
+
+impl Drop for ReadbackQueue {
+	fn drop(&mut self) {
+		for pending in self.pending.drain(..) {
+			unsafe {
+				gl::DeleteBuffers(1, &pending.buffer_name);
+				gl::DeleteSync(pending.fence);
+			}
+		}
+	}
+}
+
+
+/// Like `pixel_transfer_format`, but float formats are widened to FLOAT.
+/// Checks that `source` can be read back, so that `ReadbackQueue::start` can't fail.
+pub fn validate_source(resource_manager: &ResourceManager, source: ReadbackSource) -> Result<(), GfxError> {
+	readback_layout(resource_manager, source).map(|_| ())
+}
+
+// (size, format, type, bytes per texel)
+fn readback_layout(resource_manager: &ResourceManager, source: ReadbackSource) -> Result<(Vec2i, u32, u32, usize), GfxError> {
+	let ReadbackSource::Image(handle) = source else {
+		return Ok((resource_manager.backbuffer_size(), gl::RGBA, gl::UNSIGNED_BYTE, 4))
+	};
+
+	let image = resource_manager.resolve_image(handle)
+		.ok_or(GfxError::MissingImage(handle))?;
+
+	if image.texture_type != TextureType::Tex2D || image.samples > 1 {
+		return Err(GfxError::UnreadableImage { image: handle, texture_type: image.texture_type, samples: image.samples });
+	}
+
+	let (format, ty, texel_size) = readback_format(image.format)
+		.ok_or(GfxError::UnsupportedReadbackFormat { image: handle, format: image.format })?;
+
+	Ok((image.resolved_size, format, ty, texel_size))
+}
+
+fn readback_format(internal_format: u32) -> Option<(u32, u32, usize)> {
+	let formats = match internal_format {
+		gl::R16F => (gl::RED, gl::FLOAT, 4),
+		gl::RG16F => (gl::RG, gl::FLOAT, 8),
+		gl::RGBA16F => (gl::RGBA, gl::FLOAT, 16),
+		gl::R11F_G11F_B10F => (gl::RGB, gl::FLOAT, 12),
+		_ => return pixel_transfer_format(internal_format),
+	};
+
+	Some(formats)
+}
@@ -3,38 +3,74 @@ pub mod pipeline;
 pub mod sampler;
 pub mod image;
 pub mod fbo;
+pub mod manifest;
+pub mod source;
+pub mod error;
 
 use std::collections::HashMap;
 
 pub type ResourcePath = std::path::PathBuf;
 pub type ResourcePathRef = std::path::Path;
 
-pub use self::shader::{ShaderType, ShaderDef, ShaderObject, BlockBindingLocation};
+pub use self::shader::{ShaderType, ShaderDef, GlslProfile, ShaderObject, BlockBindingLocation, ImageBindingDescription, ImageBindingKind, ShaderCompileError};
 pub use self::pipeline::{PipelineDef, PipelineObject};
-pub use self::sampler::{SamplerDef, AddressingMode, FilterMode, SamplerObject};
-pub use self::image::{ImageDef, ImageObject, ImageSize};
-pub use self::fbo::{FboDef, FboObject};
+pub use self::sampler::{SamplerDef, AddressingMode, FilterMode, CompareFunction, SamplerObject};
+pub use self::image::{ImageDef, ImageObject, ImageSize, ImageLoadOptions, ColorSpace, MipGeneration, ChannelLayout, CubemapSource, TextureType, ImageRegion};
+pub use self::image::pixel_transfer_format;
+pub use self::image::async_load::ImageLoadState;
+pub use self::image::view::ImageViewDef;
+pub use self::fbo::{FboDef, FboObject, FboAttachment, MAX_COLOR_ATTACHMENT_POINTS};
+pub use self::manifest::Manifest;
+pub use self::error::GfxError;
+pub use self::source::{ResourceSource, DirectorySource, EmbeddedSource, ArchiveSource};
 
 use common::math::Vec2i;
+use std::time::{Duration, Instant, SystemTime};
 
 
+pub const HOT_RELOAD_POLL_INTERVAL: Duration = Duration::from_millis(500);
 
 
+
+
+// Handles are generational so that handles to destroyed resources can't resolve to whatever reuses their slot.
 #[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
-pub struct ShaderHandle(pub u32);
+pub struct ShaderHandle {
+	pub index: u32,
+	pub generation: u32,
+}
 
 #[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
-pub struct ImageHandle(pub u32);
+pub struct ImageHandle {
+	pub index: u32,
+	pub generation: u32,
+}
+
+/// The stages loaded from a single multi-stage shader file.
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, Default)]
+pub struct ProgramHandle {
+	pub vertex: Option<ShaderHandle>,
+	pub fragment: Option<ShaderHandle>,
+	pub compute: Option<ShaderHandle>,
+}
 
 
 
 #[derive(Debug)]
 pub struct ResourceManager {
-	resource_root_path: ResourcePath,
+	/// Searched last to first.
+	sources: Vec<Box<dyn ResourceSource>>,
 
 	shader_defs: HashMap<ShaderDef, ShaderHandle>,
 	shader_objects: HashMap<ShaderHandle, ShaderObject>,
-	shader_counter: u32,
+	shader_handles: HandleAllocator,
+	shader_handle_defs: HashMap<ShaderHandle, ShaderDef>,
+
+	shader_watch_states: HashMap<ShaderHandle, ShaderWatchState>,
+	hot_reload_enabled: bool,
+	last_hot_reload_poll: Instant,
+
+	program_binary_cache: Option<shader::ProgramBinaryCache>,
 
 	pipeline_objects: HashMap<PipelineDef, PipelineObject>,
 	sampler_objects: HashMap<SamplerDef, SamplerObject>,
@@ -45,14 +81,39 @@ pub struct ResourceManager {
 
 	image_defs: HashMap<ImageDef, ImageHandle>,
 	image_objects: HashMap<ImageHandle, ImageObject>,
-	image_counter: u32,
+	image_handles: HandleAllocator,
+	async_image_loader: Option<self::image::async_load::AsyncImageLoader>,
+	image_views: HashMap<ImageHandle, ImageViewDef>,
+
+	manifest: Option<Manifest>,
+
+	pending_destruction: Vec<PendingDestruction>,
 }
 
 impl ResourceManager {
 	pub fn new() -> anyhow::Result<Self> {
-		let resource_root_path = ResourcePath::from("resource");
+		let mut sources: Vec<Box<dyn ResourceSource>> = Vec::new();
 
-		anyhow::ensure!(resource_root_path.exists(), "Couldn't find resource path");
+		// Resources in the crate take lowest priority, so that they can be overridden next to the executable
+		// or in the working directory. Anything else has to be added with `add_source`.
+		let executable_dir = std::env::current_exe().ok()
+			.and_then(|path| path.parent().map(|parent| parent.join("resource")));
+
+		let default_roots = [
+			Some(ResourcePath::from(concat!(env!("CARGO_MANIFEST_DIR"), "/resource"))),
+			executable_dir,
+			Some(ResourcePath::from("resource")),
+		];
+
+		let mut default_roots: Vec<_> = default_roots.into_iter().flatten()
+			.filter(|root| root.is_dir())
+			.collect();
+
+		default_roots.dedup_by(|a, b| a.canonicalize().ok() == b.canonicalize().ok());
+
+		for root in default_roots {
+			sources.push(Box::new(DirectorySource::new(root)));
+		}
 
 		let viewport_size = unsafe {
 			let mut viewport = [0; 4];
@@ -63,11 +124,18 @@ impl ResourceManager {
 
 
 		Ok(Self{
-			resource_root_path,
+			sources,
 
 			shader_defs: HashMap::default(),
 			shader_objects: HashMap::default(),
-			shader_counter: 0,
+			shader_handles: HandleAllocator::default(),
+			shader_handle_defs: HashMap::default(),
+
+			shader_watch_states: HashMap::default(),
+			hot_reload_enabled: false,
+			last_hot_reload_poll: Instant::now(),
+
+			program_binary_cache: None,
 
 			pipeline_objects: HashMap::default(),
 			sampler_objects: HashMap::default(),
@@ -82,12 +150,37 @@ impl ResourceManager {
 
 			image_defs: HashMap::default(),
 			image_objects: HashMap::default(),
-			image_counter: 0,
+			image_handles: HandleAllocator::default(),
+			async_image_loader: None,
+			image_views: HashMap::default(),
+
+			manifest: None,
+
+			pending_destruction: Vec::new(),
 		})
 	}
 
-	pub fn resolve_path(&self, path: &ResourcePathRef) -> ResourcePath {
-		self.resource_root_path.join(path)
+	/// Sources added later take priority over earlier ones, and over the default `resource` directories.
+	pub fn add_source(&mut self, source: impl ResourceSource + 'static) {
+		self.sources.push(Box::new(source));
+	}
+
+	fn find_source(&self, path: &ResourcePathRef) -> Option<&dyn ResourceSource> {
+		self.sources.iter().rev()
+			.find(|source| source.contains(path))
+			.map(|source| &**source)
+	}
+
+	pub fn resource_exists(&self, path: &ResourcePathRef) -> bool {
+		self.find_source(path).is_some()
+	}
+
+	/// Files directly within `directory`, from the highest priority source that has any.
+	pub fn list_directory(&self, directory: &ResourcePathRef) -> Vec<ResourcePath> {
+		self.sources.iter().rev()
+			.map(|source| source.list_directory(directory))
+			.find(|files| !files.is_empty())
+			.unwrap_or_default()
 	}
 
 	pub fn backbuffer_size(&self) -> Vec2i {
@@ -99,8 +192,16 @@ impl ResourceManager {
 		self.default_fbo.viewport_size = new_size;
 
 
-		for image in self.image_objects.values_mut() {
-			if image.size != ImageSize::Backbuffer {
+		let mut resized_images = Vec::new();
+
+		for (&handle, image) in self.image_objects.iter_mut() {
+			// Views are recreated once their parents have been
+			if self.image_views.contains_key(&handle) {
+				continue
+			}
+
+			let resolved_size = image.size.resolve(new_size);
+			if resolved_size == image.resolved_size {
 				continue
 			}
 
@@ -112,26 +213,37 @@ impl ResourceManager {
 					&mut label_length, object_label.as_mut_ptr());
 
 				gl::DeleteTextures(1, &image.name);
-				gl::CreateTextures(gl::TEXTURE_2D, 1, &mut image.name);
-				gl::TextureStorage2D(image.name, 1, image.format, new_size.x, new_size.y);
+				image.name = self::image::create_texture(image.texture_type, image.format, resolved_size,
+					image.layers, image.requested_levels, image.samples);
 
 				gl::ObjectLabel(gl::TEXTURE, image.name, label_length, object_label.as_ptr());
 			}
+
+			image.resolved_size = resolved_size;
+			image.levels = image.requested_levels.min(self::image::mips::mip_count(resolved_size));
+			resized_images.push(handle);
 		}
 
+		let recreated_views = self.recreate_views_of(&resized_images);
+		resized_images.extend(recreated_views);
 
-		let mut fbo_objects = std::mem::replace(&mut self.fbo_objects, HashMap::default());
+		self.rebind_fbos_using(&resized_images);
+	}
 
-		for (def, fbo) in fbo_objects.iter_mut() {
-			fbo::resolve_and_bind(self, def, fbo);
-		}
+	pub fn load_text(&self, def: &ResourcePathRef) -> anyhow::Result<String> {
+		let data = self.load_binary(def)?;
+		let string = String::from_utf8(data)
+			.map_err(|_| anyhow::anyhow!("'{}' isn't valid UTF-8", def.display()))?;
 
-		self.fbo_objects = fbo_objects;
+		Ok(string)
 	}
 
-	pub fn load_text(&mut self, def: &ResourcePathRef) -> anyhow::Result<String> {
-		let string = std::fs::read_to_string(&self.resolve_path(def))?;
-		Ok(string)
+	pub fn load_binary(&self, def: &ResourcePathRef) -> anyhow::Result<Vec<u8>> {
+		let Some(source) = self.find_source(def) else {
+			anyhow::bail!("Couldn't find '{}' in any resource source", def.display());
+		};
+
+		source.load(def)
 	}
 
 	pub fn load_shader(&mut self, def: &ShaderDef) -> anyhow::Result<ShaderHandle> {
@@ -141,15 +253,236 @@ impl ResourceManager {
 
 		let object = self::shader::compile_shader(self, def)?;
 
-		let handle = ShaderHandle(self.shader_counter);
-		self.shader_counter += 1;
+		let (index, generation) = self.shader_handles.allocate();
+		let handle = ShaderHandle {index, generation};
+
+		self.shader_watch_states.insert(handle, ShaderWatchState {
+			last_modified: self.latest_modification_time(&object.dependencies),
+		});
 
 		self.shader_defs.insert(def.clone(), handle);
+		self.shader_handle_defs.insert(handle, def.clone());
 		self.shader_objects.insert(handle, object);
 
 		Ok(handle)
 	}
 
+	/// Loads every stage declared with `#pragma stage(...)` in a single shader file.
+	pub fn load_program(&mut self, path: impl Into<ResourcePath>) -> anyhow::Result<ProgramHandle> {
+		let path = path.into();
+		let content = self.load_text(&path)?;
+
+		let stages = shader::preprocess::declared_stages(&content)
+			.map_err(|error| anyhow::anyhow!("'{}': {error}", path.display()))?;
+
+		anyhow::ensure!(!stages.is_empty(), "'{}' doesn't declare any stages", path.display());
+
+		let mut program = ProgramHandle::default();
+
+		for stage in stages {
+			let handle = self.load_shader(&ShaderDef::new(path.clone(), stage))?;
+
+			match stage {
+				ShaderType::Vertex => program.vertex = Some(handle),
+				ShaderType::Fragment => program.fragment = Some(handle),
+				ShaderType::Compute => program.compute = Some(handle),
+			}
+		}
+
+		Ok(program)
+	}
+
+	/// Gets a variant of an already loaded shader with additional feature keys defined, compiling it if it
+	/// hasn't been requested before.
+	pub fn get_shader_variant<F>(&mut self, base: ShaderHandle, features: impl IntoIterator<Item=F>) -> anyhow::Result<ShaderHandle>
+		where F: Into<String>
+	{
+		let Some(base_def) = self.shader_handle_defs.get(&base) else {
+			anyhow::bail!("Trying to get variant of unknown shader {base:?}");
+		};
+
+		let mut variant_def = base_def.clone();
+		variant_def.features.extend(features.into_iter().map(Into::into));
+
+		if variant_def == *base_def {
+			return Ok(base);
+		}
+
+		self.load_shader(&variant_def)
+	}
+
+	/// Opt in to caching compiled program binaries in `directory`. Only affects shaders loaded after this call.
+	pub fn enable_program_binary_cache(&mut self, directory: impl Into<std::path::PathBuf>) -> anyhow::Result<()> {
+		self.program_binary_cache = Some(shader::ProgramBinaryCache::new(directory)?);
+		Ok(())
+	}
+
+	pub fn set_hot_reload_enabled(&mut self, enabled: bool) {
+		self.hot_reload_enabled = enabled;
+	}
+
+	/// Loads every resource declared in the manifest at `path`, replacing any previously loaded manifest.
+	/// If hot reload is enabled, the manifest is reloaded whenever it changes - so handles should be looked up by
+	/// name each frame rather than cached, or they may miss changes.
+	pub fn load_manifest(&mut self, path: impl Into<ResourcePath>) -> anyhow::Result<()> {
+		let path = path.into();
+		let previous = self.manifest.take();
+
+		match self::manifest::load(self, &path, previous.as_ref()) {
+			Ok(manifest) => {
+				self.replace_manifest(previous, manifest);
+				Ok(())
+			}
+
+			Err(error) => {
+				self.manifest = previous;
+				Err(error)
+			}
+		}
+	}
+
+	pub fn manifest(&self) -> Option<&Manifest> {
+		self.manifest.as_ref()
+	}
+
+	pub fn shader_by_name(&self, name: &str) -> Option<ShaderHandle> {
+		self.manifest.as_ref()?.shader(name)
+	}
+
+	pub fn program_by_name(&self, name: &str) -> Option<ProgramHandle> {
+		self.manifest.as_ref()?.program(name)
+	}
+
+	/// Includes render targets.
+	pub fn image_by_name(&self, name: &str) -> Option<ImageHandle> {
+		self.manifest.as_ref()?.image(name)
+	}
+
+	pub fn sampler_by_name(&self, name: &str) -> Option<SamplerDef> {
+		self.manifest.as_ref()?.sampler(name)
+	}
+
+	fn replace_manifest(&mut self, previous: Option<Manifest>, manifest: Manifest) {
+		if let Some(previous) = previous {
+			// Render targets aren't shared, so anything the new manifest didn't carry over is no longer used
+			let current_handles: Vec<_> = manifest.render_target_handles().collect();
+			let stale_handles: Vec<_> = previous.render_target_handles()
+				.filter(|handle| !current_handles.contains(handle))
+				.collect();
+
+			for handle in stale_handles {
+				self.destroy_image(handle);
+			}
+		}
+
+		self.manifest = Some(manifest);
+	}
+
+	fn reload_changed_manifest(&mut self) {
+		let Some(manifest) = &self.manifest else {
+			return
+		};
+
+		let last_modified = self.latest_modification_time(&[manifest.path.clone()]);
+		if last_modified <= manifest.last_modified {
+			return
+		}
+
+		let mut previous = self.manifest.take().unwrap();
+
+		// Update the timestamp even on failure so we don't keep trying to load a broken manifest every poll
+		previous.last_modified = last_modified;
+
+		match self::manifest::load(self, &previous.path.clone(), Some(&previous)) {
+			Ok(manifest) => {
+				println!("Reloaded manifest '{}'", manifest.path.display());
+				self.replace_manifest(Some(previous), manifest);
+			}
+
+			Err(error) => {
+				eprintln!("Failed to reload manifest '{}' - keeping previous version\n{error:#}", previous.path.display());
+				self.manifest = Some(previous);
+			}
+		}
+	}
+
+	/// Recompiles any shaders whose source files have changed since they were last compiled, and rebuilds
+	/// any pipelines that use them. Shaders that fail to compile keep their previous program.
+	/// Also reloads the manifest if it has changed.
+	pub fn reload_changed_shaders(&mut self) {
+		if !self.hot_reload_enabled || self.last_hot_reload_poll.elapsed() < HOT_RELOAD_POLL_INTERVAL {
+			return
+		}
+
+		self.last_hot_reload_poll = Instant::now();
+
+		self.reload_changed_manifest();
+
+		let mut changed_shaders = Vec::new();
+
+		for (&handle, watch_state) in self.shader_watch_states.iter() {
+			let dependencies = &self.shader_objects[&handle].dependencies;
+			let last_modified = self.latest_modification_time(dependencies);
+
+			if last_modified > watch_state.last_modified {
+				changed_shaders.push((handle, self.shader_handle_defs[&handle].clone(), last_modified));
+			}
+		}
+
+		if changed_shaders.is_empty() {
+			return
+		}
+
+		let mut reloaded_shaders = Vec::new();
+
+		for (handle, def, last_modified) in changed_shaders {
+			// Update the timestamp even on failure so we don't keep trying to compile a broken shader every poll
+			self.shader_watch_states.get_mut(&handle).unwrap().last_modified = last_modified;
+
+			match self::shader::compile_shader(self, &def) {
+				Ok(object) => {
+					println!("Reloaded shader '{}'", def.path.display());
+
+					let prev_object = self.shader_objects.insert(handle, object).unwrap();
+					self.defer_destruction(DestroyedResource::Program(prev_object.name));
+
+					reloaded_shaders.push(handle);
+				}
+
+				Err(error) => {
+					eprintln!("Failed to reload shader '{}' - keeping previous version\n{error}", def.path.display());
+				}
+			}
+		}
+
+		// Rebuild pipelines using reloaded shaders so that reflection information stays up to date
+		let invalidated_pipelines: Vec<_> = self.pipeline_objects.keys()
+			.filter(|def| def.shaders().any(|shader| reloaded_shaders.contains(&shader)))
+			.cloned()
+			.collect();
+
+		for def in invalidated_pipelines {
+			let prev_object = self.pipeline_objects.remove(&def).unwrap();
+			self.defer_destruction(DestroyedResource::Pipeline(prev_object.name));
+
+			match self::pipeline::create_pipeline(self, &def) {
+				Ok(object) => {
+					self.pipeline_objects.insert(def, object);
+				}
+
+				// get_pipeline will try again on next use
+				Err(error) => eprintln!("Failed to rebuild pipeline after shader reload: {error}"),
+			}
+		}
+	}
+
+	fn latest_modification_time(&self, paths: &[ResourcePath]) -> SystemTime {
+		paths.iter()
+			.filter_map(|path| self.find_source(path)?.modification_time(path))
+			.max()
+			.unwrap_or(SystemTime::UNIX_EPOCH)
+	}
+
 	pub fn load_image(&mut self, def: &ImageDef) -> anyhow::Result<ImageHandle> {
 		let is_shared = def.is_shared();
 
@@ -161,8 +494,8 @@ impl ResourceManager {
 
 		let object = self::image::load(self, def)?;
 
-		let handle = ImageHandle(self.image_counter);
-		self.image_counter += 1;
+		let (index, generation) = self.image_handles.allocate();
+		let handle = ImageHandle {index, generation};
 
 		if is_shared {
 			self.image_defs.insert(def.clone(), handle);
@@ -173,7 +506,264 @@ impl ResourceManager {
 		Ok(handle)
 	}
 
-	pub fn get_pipeline<'s>(&'s mut self, def: &'_ PipelineDef) -> anyhow::Result<&'s PipelineObject> {
+	/// Returns a handle immediately that resolves to a placeholder until the image has been decoded on a worker thread
+	/// and uploaded. Only plain images loaded from a path are decoded in the background, anything else loads synchronously.
+	pub fn load_image_async(&mut self, def: &ImageDef) -> anyhow::Result<ImageHandle> {
+		let ImageDef::Path{path, options} = def else {
+			return self.load_image(def)
+		};
+
+		if self::image::compressed::is_compressed_container(path) {
+			return self.load_image(def)
+		}
+
+		if let Some(handle) = self.image_defs.get(def) {
+			return Ok(*handle);
+		}
+
+		// TODO(pat.m): reading happens on this thread since sources aren't Send. Only decoding is in the background.
+		let data = self.load_binary(path)?;
+		let placeholder = self::image::create_placeholder()?;
+
+		let (index, generation) = self.image_handles.allocate();
+		let handle = ImageHandle {index, generation};
+
+		self.image_defs.insert(def.clone(), handle);
+		self.image_objects.insert(handle, placeholder);
+
+		self.async_image_loader.get_or_insert_with(self::image::async_load::AsyncImageLoader::new)
+			.request(handle, path.clone(), data, options.clone());
+
+		Ok(handle)
+	}
+
+	/// None if `handle` doesn't refer to a live image.
+	pub fn image_load_state(&self, handle: ImageHandle) -> Option<ImageLoadState> {
+		if !self.image_objects.contains_key(&handle) {
+			return None
+		}
+
+		let state = self.async_image_loader.as_ref()
+			.and_then(|loader| loader.state(handle))
+			.unwrap_or(ImageLoadState::Loaded);
+
+		Some(state)
+	}
+
+	/// Uploads images that have finished decoding in the background, and swaps them in for their placeholders.
+	pub fn process_async_image_loads(&mut self) {
+		let Some(loader) = &mut self.async_image_loader else {
+			return
+		};
+
+		let finished_images = loader.process();
+		self.replace_image_objects(finished_images);
+	}
+
+	/// Swaps new storage in for existing images without invalidating their handles.
+	/// Views of them are recreated and framebuffers using them are rebound.
+	fn replace_image_objects(&mut self, replacements: Vec<(ImageHandle, ImageObject)>) {
+		if replacements.is_empty() {
+			return
+		}
+
+		let mut replaced_images = Vec::new();
+
+		for (handle, object) in replacements {
+			if let Some(previous_object) = self.image_objects.insert(handle, object) {
+				self.defer_destruction(DestroyedResource::Texture(previous_object.name));
+			}
+
+			replaced_images.push(handle);
+		}
+
+		let recreated_views = self.recreate_views_of(&replaced_images);
+		replaced_images.extend(recreated_views);
+
+		self.rebind_fbos_using(&replaced_images);
+	}
+
+	/// Creates an image sharing the storage of `image`, reinterpreted as `format` and restricted to a range of
+	/// mip levels and layers. Views can be bound and attached like any other image, and are recreated along with
+	/// their parent when it's resized. Destroying the parent destroys its views.
+	pub fn create_image_view(&mut self, image: ImageHandle, format: u32, levels: std::ops::Range<u32>, layers: std::ops::Range<u32>)
+		-> anyhow::Result<ImageHandle>
+	{
+		let Some(parent) = self.image_objects.get(&image) else {
+			anyhow::bail!("Trying to create view of unknown image {image:?}");
+		};
+
+		let def = ImageViewDef { parent: image, format, levels, layers };
+		let object = self::image::view::create_view(parent, &def)?;
+
+		let (index, generation) = self.image_handles.allocate();
+		let handle = ImageHandle {index, generation};
+
+		self.image_objects.insert(handle, object);
+		self.image_views.insert(handle, def);
+
+		Ok(handle)
+	}
+
+	/// Recreates views of `parents` after their storage has been replaced, and any views of those views.
+	/// Returns every view that was recreated.
+	fn recreate_views_of(&mut self, parents: &[ImageHandle]) -> Vec<ImageHandle> {
+		let mut recreated_views = Vec::new();
+		let mut changed_images = parents.to_vec();
+
+		while !changed_images.is_empty() {
+			let affected_views: Vec<_> = self.image_views.iter()
+				.filter(|(_, def)| changed_images.contains(&def.parent))
+				.map(|(&handle, def)| (handle, def.clone()))
+				.collect();
+
+			changed_images.clear();
+
+			for (handle, def) in affected_views {
+				let parent = &self.image_objects[&def.parent];
+
+				match self::image::view::create_view(parent, &def) {
+					Ok(object) => {
+						let prev_object = self.image_objects.insert(handle, object).unwrap();
+						self.defer_destruction(DestroyedResource::Texture(prev_object.name));
+
+						changed_images.push(handle);
+						recreated_views.push(handle);
+					}
+
+					// The previous view keeps the old storage alive, so it's still safe to use - just stale
+					Err(error) => eprintln!("Failed to recreate image view after its parent changed - keeping previous version\n{error}"),
+				}
+			}
+		}
+
+		recreated_views
+	}
+
+	fn rebind_fbos_using(&mut self, images: &[ImageHandle]) {
+		let mut fbo_objects = std::mem::replace(&mut self.fbo_objects, HashMap::default());
+		let mut broken_fbos = Vec::new();
+
+		for (def, fbo) in fbo_objects.iter_mut() {
+			if !def.images().any(|image| images.contains(&image)) {
+				continue
+			}
+
+			if let Err(error) = fbo::resolve_and_bind(self, def, fbo) {
+				eprintln!("Failed to rebind framebuffer after its images changed: {error}");
+				broken_fbos.push(def.clone());
+			}
+		}
+
+		// Evict broken framebuffers so that the error is reported again by get_fbo next time they're used
+		for def in broken_fbos {
+			let fbo = fbo_objects.remove(&def).unwrap();
+			self.defer_destruction(DestroyedResource::Framebuffer(fbo.name));
+		}
+
+		self.fbo_objects = fbo_objects;
+	}
+
+	/// Destroys a shader along with any cached pipelines that use it.
+	/// The program itself is only deleted once the GPU has finished with any previously submitted work.
+	pub fn destroy_shader(&mut self, handle: ShaderHandle) {
+		let Some(object) = self.shader_objects.remove(&handle) else {
+			return
+		};
+
+		self.shader_handles.free(handle.index);
+		self.shader_defs.retain(|_, &mut defs_handle| defs_handle != handle);
+		self.shader_handle_defs.remove(&handle);
+		self.shader_watch_states.remove(&handle);
+
+		let evicted_pipelines: Vec<_> = self.pipeline_objects.keys()
+			.filter(|def| def.shaders().any(|shader| shader == handle))
+			.cloned()
+			.collect();
+
+		for def in evicted_pipelines {
+			let pipeline = self.pipeline_objects.remove(&def).unwrap();
+			self.defer_destruction(DestroyedResource::Pipeline(pipeline.name));
+		}
+
+		self.defer_destruction(DestroyedResource::Program(object.name));
+	}
+
+	/// Destroys an image along with its views, and any cached framebuffers that use it as an attachment.
+	/// The texture itself is only deleted once the GPU has finished with any previously submitted work.
+	pub fn destroy_image(&mut self, handle: ImageHandle) {
+		let Some(object) = self.image_objects.remove(&handle) else {
+			return
+		};
+
+		self.image_handles.free(handle.index);
+		self.image_defs.retain(|_, &mut defs_handle| defs_handle != handle);
+		self.image_views.remove(&handle);
+
+		let evicted_fbos: Vec<_> = self.fbo_objects.keys()
+			.filter(|def| def.images().any(|image| image == handle))
+			.cloned()
+			.collect();
+
+		for def in evicted_fbos {
+			let fbo = self.fbo_objects.remove(&def).unwrap();
+			self.defer_destruction(DestroyedResource::Framebuffer(fbo.name));
+		}
+
+		self.defer_destruction(DestroyedResource::Texture(object.name));
+
+		let partial_upload = self.async_image_loader.as_mut()
+			.and_then(|loader| loader.cancel(handle));
+
+		if let Some(texture_name) = partial_upload {
+			self.defer_destruction(DestroyedResource::Texture(texture_name));
+		}
+
+		let views: Vec<_> = self.image_views.iter()
+			.filter(|(_, def)| def.parent == handle)
+			.map(|(&view, _)| view)
+			.collect();
+
+		for view in views {
+			self.destroy_image(view);
+		}
+	}
+
+	fn defer_destruction(&mut self, resource: DestroyedResource) {
+		let fence = unsafe {
+			gl::FenceSync(gl::SYNC_GPU_COMMANDS_COMPLETE, 0)
+		};
+
+		self.pending_destruction.push(PendingDestruction {fence, resource});
+	}
+
+	/// Deletes GL objects for destroyed resources that the GPU is no longer using.
+	pub fn process_pending_destruction(&mut self) {
+		self.pending_destruction.retain(|pending| {
+			let result = unsafe {
+				gl::ClientWaitSync(pending.fence, 0, 0)
+			};
+
+			if result != gl::ALREADY_SIGNALED && result != gl::CONDITION_SATISFIED {
+				return true
+			}
+
+			unsafe {
+				gl::DeleteSync(pending.fence);
+
+				match pending.resource {
+					DestroyedResource::Program(name) => gl::DeleteProgram(name),
+					DestroyedResource::Pipeline(name) => gl::DeleteProgramPipelines(1, &name),
+					DestroyedResource::Texture(name) => gl::DeleteTextures(1, &name),
+					DestroyedResource::Framebuffer(name) => gl::DeleteFramebuffers(1, &name),
+				}
+			}
+
+			false
+		});
+	}
+
+	pub fn get_pipeline<'s>(&'s mut self, def: &'_ PipelineDef) -> Result<&'s PipelineObject, GfxError> {
 		// HACK: I can't figure out the lifetimes for this - something goes weird if I try to use if let = get here
 		// see: https://users.rust-lang.org/t/lifetime-is-not-dropped-after-if-let-x-return-x/42892
 		if self.pipeline_objects.contains_key(def) {
@@ -190,8 +780,8 @@ impl ResourceManager {
 			.or_insert_with(|| self::sampler::create_sampler(def))
 	}
 
-	pub fn get_fbo<'s>(&'s mut self, def: &'_ FboDef) -> anyhow::Result<&'s FboObject> {
-		if def == &FboDef::default() {
+	pub fn get_fbo<'s>(&'s mut self, def: &'_ FboDef) -> Result<&'s FboObject, GfxError> {
+		if def.is_default() {
 			return Ok(&self.default_fbo);
 		}
 
@@ -217,3 +807,142 @@ impl ResourceManager {
 }
 
 
+
+#[derive(Debug)]
+struct ShaderWatchState {
+	last_modified: SystemTime,
+}
+
+
+
+impl Drop for ResourceManager {
+	fn drop(&mut self) {
+		if cfg!(debug_assertions) {
+			self.report_live_resources();
+		}
+
+		unsafe {
+			// Make sure nothing we're about to delete is still in use
+			gl::Finish();
+
+			for pending in self.pending_destruction.drain(..) {
+				gl::DeleteSync(pending.fence);
+
+				match pending.resource {
+					DestroyedResource::Program(name) => gl::DeleteProgram(name),
+					DestroyedResource::Pipeline(name) => gl::DeleteProgramPipelines(1, &name),
+					DestroyedResource::Texture(name) => gl::DeleteTextures(1, &name),
+					DestroyedResource::Framebuffer(name) => gl::DeleteFramebuffers(1, &name),
+				}
+			}
+
+			for (_, fbo) in self.fbo_objects.drain() {
+				gl::DeleteFramebuffers(1, &fbo.name);
+			}
+
+			for (_, pipeline) in self.pipeline_objects.drain() {
+				gl::DeleteProgramPipelines(1, &pipeline.name);
+			}
+
+			for (_, sampler) in self.sampler_objects.drain() {
+				gl::DeleteSamplers(1, &sampler.name);
+			}
+
+			for (_, shader) in self.shader_objects.drain() {
+				gl::DeleteProgram(shader.name);
+			}
+
+			for (_, image) in self.image_objects.drain() {
+				gl::DeleteTextures(1, &image.name);
+			}
+		}
+	}
+}
+
+impl ResourceManager {
+	fn report_live_resources(&self) {
+		let num_live_objects = self.shader_objects.len() + self.image_objects.len() + self.pipeline_objects.len()
+			+ self.sampler_objects.len() + self.fbo_objects.len() + self.pending_destruction.len();
+
+		if num_live_objects == 0 {
+			return
+		}
+
+		eprintln!("ResourceManager dropped with {num_live_objects} live GL objects:");
+
+		for (handle, object) in self.shader_objects.iter() {
+			match self.shader_handle_defs.get(handle) {
+				Some(def) => eprintln!("    shader {handle:?} (program {}): {:?} '{}'", object.name, def.shader_type, def.path.display()),
+				None => eprintln!("    shader {handle:?} (program {})", object.name),
+			}
+		}
+
+		for (handle, object) in self.image_objects.iter() {
+			let def = self.image_defs.iter()
+				.find(|(_, defs_handle)| **defs_handle == *handle)
+				.map(|(def, _)| def);
+
+			match def {
+				Some(def) => eprintln!("    image {handle:?} (texture {}): {def:?}", object.name),
+				None => eprintln!("    image {handle:?} (texture {}): format {:#x}, size {:?}", object.name, object.format, object.size),
+			}
+		}
+
+		for (def, object) in self.pipeline_objects.iter() {
+			eprintln!("    pipeline {} referencing shaders {:?}", object.name, def.shaders().collect::<Vec<_>>());
+		}
+
+		for (def, object) in self.fbo_objects.iter() {
+			eprintln!("    framebuffer {} referencing images {:?}", object.name, def.images().collect::<Vec<_>>());
+		}
+
+		for (def, object) in self.sampler_objects.iter() {
+			eprintln!("    sampler {}: {def:?}", object.name);
+		}
+
+		for pending in self.pending_destruction.iter() {
+			eprintln!("    pending destruction: {:?}", pending.resource);
+		}
+	}
+}
+
+
+
+#[derive(Debug, Default)]
+struct HandleAllocator {
+	generations: Vec<u32>,
+	free_indices: Vec<u32>,
+}
+
+impl HandleAllocator {
+	fn allocate(&mut self) -> (u32, u32) {
+		if let Some(index) = self.free_indices.pop() {
+			return (index, self.generations[index as usize]);
+		}
+
+		let index = self.generations.len() as u32;
+		self.generations.push(0);
+		(index, 0)
+	}
+
+	fn free(&mut self, index: u32) {
+		self.generations[index as usize] += 1;
+		self.free_indices.push(index);
+	}
+}
+
+
+
+#[derive(Debug)]
+enum DestroyedResource {
+	Program(u32),
+	Pipeline(u32),
+	Texture(u32),
+	Framebuffer(u32),
+}
+
+#[derive(Debug)]
+struct PendingDestruction {
+	fence: gl::types::GLsync,
+	resource: DestroyedResource,
+}
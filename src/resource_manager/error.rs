@@ -0,0 +1,241 @@
+use super::{ImageHandle, ShaderHandle, ImageBindingKind, ImageRegion, TextureType};
+use common::math::Vec2i;
+
+
+/// Errors from creating or binding GPU objects that tools may want to report rather than crash on.
+#[derive(Clone, Debug, PartialEq)]
+pub enum GfxError {
+	/// `status` is the result of glCheckNamedFramebufferStatus.
+	IncompleteFramebuffer {
+		status: u32,
+	},
+
+	/// Every attachment of a framebuffer must have the same size.
+	MismatchedAttachmentSizes {
+		attachment_point: u32,
+		expected: Vec2i,
+		found: Vec2i,
+	},
+
+	/// More colour attachments than GL_MAX_COLOR_ATTACHMENTS or GL_MAX_DRAW_BUFFERS allow.
+	TooManyColorAttachments {
+		count: usize,
+		max: usize,
+	},
+
+	/// A combined depth/stencil attachment was given along with a separate depth or stencil attachment.
+	ConflictingDepthStencilAttachments,
+
+	/// Two stages of a pipeline declare a uniform or storage block with the same name but a different layout or binding.
+	IncompatibleInterfaceBlocks {
+		name: String,
+	},
+
+	/// Two stages of a pipeline declare an image or sampler uniform with the same name but a different type or unit.
+	IncompatibleImageBindings {
+		name: String,
+	},
+
+	/// A pass resolves an attachment it doesn't have.
+	MissingResolveSource {
+		pass: String,
+	},
+
+	/// Multisample resolves can't scale, so the source and destination must be the same size.
+	MismatchedResolveSize {
+		pass: String,
+		source: Vec2i,
+		destination: Vec2i,
+	},
+
+	/// A command bound a uniform or storage block by a name that none of its shaders declare.
+	UnknownBlockBinding {
+		name: String,
+	},
+
+	/// A command bound an image by a name that none of its shaders declare.
+	UnknownImageBinding {
+		name: String,
+	},
+
+	/// A texture was bound to an image uniform, or the reverse.
+	MismatchedImageBindingKind {
+		name: String,
+		expected: ImageBindingKind,
+		found: ImageBindingKind,
+	},
+
+	/// An image was bound to an image unit whose declared format it can't be reinterpreted as.
+	IncompatibleImageFormat {
+		unit: u32,
+		image_format: u32,
+		unit_format: u32,
+	},
+
+	/// Depth can only be resolved into an image of exactly the same format.
+	MismatchedResolveFormat {
+		pass: String,
+		source_format: u32,
+		destination_format: u32,
+	},
+
+	/// An image binding selected a mip level the image doesn't have.
+	InvalidImageBindingLevel {
+		image: ImageHandle,
+		level: u32,
+		levels: u32,
+	},
+
+	/// An image binding selected a single layer, face or slice that the image doesn't have at the bound level.
+	InvalidImageBindingLayer {
+		image: ImageHandle,
+		layer: u32,
+		layers: u32,
+	},
+
+	/// A multisampled image was bound to a single sampled uniform, or the reverse.
+	MultisampleMismatch {
+		unit: u32,
+		image_samples: u32,
+	},
+
+	/// Only single sampled 2D images can be read back.
+	UnreadableImage {
+		image: ImageHandle,
+		texture_type: TextureType,
+		samples: u32,
+	},
+
+	UnsupportedReadbackFormat {
+		image: ImageHandle,
+		format: u32,
+	},
+
+	UnsupportedUploadFormat {
+		image: ImageHandle,
+		format: u32,
+	},
+
+	/// Uploads must be tightly packed texels in the image's format.
+	MismatchedUploadSize {
+		image: ImageHandle,
+		expected: usize,
+		found: usize,
+	},
+
+	/// The region isn't within a single level and layer of the image.
+	UploadOutOfBounds {
+		image: ImageHandle,
+		region: ImageRegion,
+	},
+
+	MissingImage(ImageHandle),
+	MissingShader(ShaderHandle),
+
+	/// `reason` is the error from compiling the variant.
+	ShaderVariantFailed {
+		shader: ShaderHandle,
+		features: Vec<String>,
+		reason: String,
+	},
+
+	PipelineCreationFailed,
+}
+
+impl GfxError {
+	fn framebuffer_status_reason(status: u32) -> &'static str {
+		match status {
+			gl::FRAMEBUFFER_UNDEFINED => "default framebuffer doesn't exist",
+			gl::FRAMEBUFFER_INCOMPLETE_ATTACHMENT => "an attachment is incomplete",
+			gl::FRAMEBUFFER_INCOMPLETE_MISSING_ATTACHMENT => "no images are attached",
+			gl::FRAMEBUFFER_INCOMPLETE_DRAW_BUFFER => "a draw buffer has no attachment",
+			gl::FRAMEBUFFER_INCOMPLETE_READ_BUFFER => "the read buffer has no attachment",
+			gl::FRAMEBUFFER_UNSUPPORTED => "the combination of attachment formats is unsupported",
+			gl::FRAMEBUFFER_INCOMPLETE_MULTISAMPLE => "attachments have different sample counts",
+			gl::FRAMEBUFFER_INCOMPLETE_LAYER_TARGETS => "attachments are a mix of layered and non-layered",
+			_ => "unknown status",
+		}
+	}
+}
+
+impl std::error::Error for GfxError {}
+
+impl std::fmt::Display for GfxError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			GfxError::IncompleteFramebuffer{status} =>
+				write!(f, "Framebuffer is incomplete: {} ({status:#x})", GfxError::framebuffer_status_reason(*status)),
+
+			GfxError::MismatchedAttachmentSizes{attachment_point, expected, found} =>
+				write!(f, "Framebuffer attachment {attachment_point:#x} is {found:?}, but other attachments are {expected:?}"),
+
+			GfxError::TooManyColorAttachments{count, max} =>
+				write!(f, "Framebuffer has {count} colour attachments, but only {max} are supported"),
+
+			GfxError::ConflictingDepthStencilAttachments =>
+				write!(f, "Framebuffer has both a combined depth/stencil attachment and a separate depth or stencil attachment"),
+
+			GfxError::IncompatibleInterfaceBlocks{name} =>
+				write!(f, "Pipeline contains multiple incompatible interface blocks with same name '{name}'"),
+
+			GfxError::IncompatibleImageBindings{name} =>
+				write!(f, "Pipeline contains multiple incompatible image bindings with same name '{name}'"),
+
+			GfxError::MissingResolveSource{pass} =>
+				write!(f, "Pass '{pass}' resolves an attachment that isn't attached"),
+
+			GfxError::MismatchedResolveSize{pass, source, destination} =>
+				write!(f, "Pass '{pass}' resolves a {source:?} attachment into a {destination:?} image - sizes must match"),
+
+			GfxError::MismatchedResolveFormat{pass, source_format, destination_format} =>
+				write!(f, "Pass '{pass}' resolves a depth attachment with format {source_format:#x} into an image with format {destination_format:#x} - formats must match"),
+
+			GfxError::UnknownBlockBinding{name} =>
+				write!(f, "Couldn't find block binding with name '{name}'"),
+
+			GfxError::UnknownImageBinding{name} =>
+				write!(f, "Couldn't find image binding with name '{name}'"),
+
+			GfxError::MismatchedImageBindingKind{name, expected, found} =>
+				write!(f, "Image binding '{name}' is a {expected:?} uniform but was bound as {found:?}"),
+
+			GfxError::IncompatibleImageFormat{unit, image_format, unit_format} =>
+				write!(f, "Image format {image_format:#x} is incompatible with format {unit_format:#x} expected by image unit {unit}"),
+
+			GfxError::InvalidImageBindingLevel{image, level, levels} =>
+				write!(f, "Binding mip level {level} of {image:?}, which only has {levels} levels"),
+
+			GfxError::InvalidImageBindingLayer{image, layer, layers} =>
+				write!(f, "Binding layer {layer} of {image:?}, which only has {layers} layers at the bound level"),
+
+			GfxError::MultisampleMismatch{unit, image_samples: 1} =>
+				write!(f, "Single sampled image bound to multisampled uniform at unit {unit}"),
+
+			GfxError::MultisampleMismatch{unit, image_samples} =>
+				write!(f, "Image with {image_samples} samples bound to single sampled uniform at unit {unit}"),
+
+			GfxError::UnreadableImage{image, texture_type, samples} =>
+				write!(f, "Only single sampled 2D images can be read back, but {image:?} is a {texture_type:?} with {samples} samples"),
+
+			GfxError::UnsupportedReadbackFormat{image, format} =>
+				write!(f, "Can't read back {image:?} with format {format:#x}"),
+
+			GfxError::UnsupportedUploadFormat{image, format} =>
+				write!(f, "Can't upload pixels to {image:?} with format {format:#x}"),
+
+			GfxError::MismatchedUploadSize{image, expected, found} =>
+				write!(f, "Expected {expected} bytes of pixel data for upload to {image:?}, got {found}"),
+
+			GfxError::UploadOutOfBounds{image, region} =>
+				write!(f, "Upload region {region:?} is outside of {image:?}"),
+
+			GfxError::MissingImage(handle) => write!(f, "Failed to resolve {handle:?} - probably use after delete"),
+			GfxError::MissingShader(handle) => write!(f, "Failed to resolve {handle:?} - probably use after delete"),
+
+			GfxError::ShaderVariantFailed{shader, features, reason} =>
+				write!(f, "Failed to get variant of {shader:?} with features {features:?}: {reason}"),
+
+			GfxError::PipelineCreationFailed => write!(f, "Failed to create program pipeline"),
+		}
+	}
+}
@@ -1,15 +1,98 @@
-
-use super::{ResourceManager, ImageHandle};
+use super::{ResourceManager, ImageHandle, ImageObject, GfxError};
 use common::math::Vec2i;
 
 
+/// Upper bound on stored colour attachment points, well above what any implementation supports.
+/// Points beyond it aren't stored, but still fail framebuffer creation with `GfxError::TooManyColorAttachments`.
+pub const MAX_COLOR_ATTACHMENT_POINTS: u32 = 32;
+
+
 #[derive(Hash, Clone, Default, Debug, Eq, PartialEq)]
 pub struct FboDef {
-	pub color_attachment_0: Option<ImageHandle>,
-	pub color_attachment_1: Option<ImageHandle>,
-	pub color_attachment_2: Option<ImageHandle>,
-	pub color_attachment_3: Option<ImageHandle>,
-	pub depth_stencil_attachment: Option<ImageHandle>,
+	/// Indexed by attachment point - None leaves a gap, which gets a draw buffer of GL_NONE.
+	pub color_attachments: Vec<Option<FboAttachment>>,
+
+	/// For depth-only formats, or to attach just the depth of a combined depth/stencil image.
+	pub depth_attachment: Option<FboAttachment>,
+	/// For stencil-only formats, or to attach just the stencil of a combined depth/stencil image.
+	pub stencil_attachment: Option<FboAttachment>,
+	/// Can't be used together with `depth_attachment` or `stencil_attachment`.
+	pub depth_stencil_attachment: Option<FboAttachment>,
+
+	/// The highest colour attachment point set at or above MAX_COLOR_ATTACHMENT_POINTS, reported when the framebuffer is created.
+	pub out_of_range_color_attachment: Option<u32>,
+}
+
+#[derive(Hash, Copy, Clone, Debug, Eq, PartialEq)]
+pub struct FboAttachment {
+	pub image: ImageHandle,
+
+	/// The mip level to render into.
+	pub level: u32,
+
+	/// Attach a single array layer or cubemap face instead of the whole image.
+	/// Faces of cube arrays are addressed as `cube * 6 + face`.
+	pub layer: Option<u32>,
+}
+
+impl From<ImageHandle> for FboAttachment {
+	fn from(image: ImageHandle) -> FboAttachment {
+		FboAttachment { image, level: 0, layer: None }
+	}
+}
+
+impl FboAttachment {
+	pub fn level(mut self, level: u32) -> FboAttachment {
+		self.level = level;
+		self
+	}
+
+	pub fn layer(mut self, layer: u32) -> FboAttachment {
+		self.layer = Some(layer);
+		self
+	}
+}
+
+impl FboDef {
+	pub fn images(&self) -> impl Iterator<Item=ImageHandle> + '_ {
+		self.color_attachments.iter().flatten()
+			.chain([&self.depth_attachment, &self.stencil_attachment, &self.depth_stencil_attachment].into_iter().flatten())
+			.map(|attachment| attachment.image)
+	}
+
+	/// Whether this describes the default framebuffer.
+	pub fn is_default(&self) -> bool {
+		self.images().next().is_none() && self.out_of_range_color_attachment.is_none()
+	}
+
+	/// Including any attachment points beyond MAX_COLOR_ATTACHMENT_POINTS.
+	pub fn color_attachment_count(&self) -> usize {
+		let out_of_range_count = self.out_of_range_color_attachment.map_or(0, |point| point as usize + 1);
+		self.color_attachments.len().max(out_of_range_count)
+	}
+
+	/// The read buffer framebuffers are created with - the first draw buffer, which may be GL_NONE.
+	pub fn default_read_buffer(&self) -> u32 {
+		match self.color_attachments.first() {
+			Some(Some(_)) => gl::COLOR_ATTACHMENT0,
+			_ => gl::NONE,
+		}
+	}
+
+	pub fn set_color_attachment(&mut self, attachment_point: u32, attachment: FboAttachment) {
+		if attachment_point >= MAX_COLOR_ATTACHMENT_POINTS {
+			self.out_of_range_color_attachment = self.out_of_range_color_attachment.max(Some(attachment_point));
+			return
+		}
+
+		let index = attachment_point as usize;
+
+		if self.color_attachments.len() <= index {
+			self.color_attachments.resize(index + 1, None);
+		}
+
+		self.color_attachments[index] = Some(attachment);
+	}
 }
 
 #[derive(Debug)]
@@ -20,7 +103,7 @@ pub struct FboObject {
 
 
 pub(super) fn create(resource_manager: &ResourceManager, def: &FboDef)
-	-> anyhow::Result<FboObject>
+	-> Result<FboObject, GfxError>
 {
 	let mut name = 0;
 
@@ -30,74 +113,111 @@ pub(super) fn create(resource_manager: &ResourceManager, def: &FboDef)
 
 	let mut object = FboObject {name, viewport_size: Vec2i::zero()};
 
-	resolve_and_bind(resource_manager, def, &mut object);
+	if let Err(error) = resolve_and_bind(resource_manager, def, &mut object) {
+		unsafe {
+			gl::DeleteFramebuffers(1, &name);
+		}
+
+		return Err(error)
+	}
 
 	Ok(object)
 }
 
 pub(super) fn resolve_and_bind(resource_manager: &ResourceManager, def: &FboDef,
-	fbo: &mut FboObject)
+	fbo: &mut FboObject) -> Result<(), GfxError>
 {
-	let mut common_size = None;
-
-	if let Some(handle) = def.color_attachment_0 {
-		let image = resource_manager.resolve_image(handle).unwrap();
-
-		common_size = Some(image.size);
+	let max_color_attachments = unsafe {
+		let mut max_attachments = 0;
+		let mut max_draw_buffers = 0;
+		gl::GetIntegerv(gl::MAX_COLOR_ATTACHMENTS, &mut max_attachments);
+		gl::GetIntegerv(gl::MAX_DRAW_BUFFERS, &mut max_draw_buffers);
+		max_attachments.min(max_draw_buffers) as usize
+	};
+
+	if def.color_attachment_count() > max_color_attachments {
+		return Err(GfxError::TooManyColorAttachments {
+			count: def.color_attachment_count(),
+			max: max_color_attachments,
+		})
+	}
 
-		unsafe {
-			gl::NamedFramebufferTexture(fbo.name, gl::COLOR_ATTACHMENT0, image.name, 0);
-		}
+	if def.depth_stencil_attachment.is_some() && (def.depth_attachment.is_some() || def.stencil_attachment.is_some()) {
+		return Err(GfxError::ConflictingDepthStencilAttachments)
 	}
 
-	if let Some(handle) = def.color_attachment_1 {
-		let image = resource_manager.resolve_image(handle).unwrap();
+	let color_attachments = def.color_attachments.iter().enumerate()
+		.map(|(index, attachment)| (gl::COLOR_ATTACHMENT0 + index as u32, *attachment));
 
-		assert!(common_size == None || common_size == Some(image.size));
-		common_size = Some(image.size);
+	let other_attachments = [
+		(gl::DEPTH_ATTACHMENT, def.depth_attachment),
+		(gl::STENCIL_ATTACHMENT, def.stencil_attachment),
+		(gl::DEPTH_STENCIL_ATTACHMENT, def.depth_stencil_attachment),
+	];
 
-		unsafe {
-			gl::NamedFramebufferTexture(fbo.name, gl::COLOR_ATTACHMENT1, image.name, 0);
-		}
-	}
+	let mut common_size = None;
 
-	if let Some(handle) = def.color_attachment_2 {
-		let image = resource_manager.resolve_image(handle).unwrap();
+	for (attachment_point, attachment) in color_attachments.chain(other_attachments) {
+		let Some(attachment) = attachment else { continue };
+
+		let image = resource_manager.resolve_image(attachment.image)
+			.ok_or(GfxError::MissingImage(attachment.image))?;
+
+		let level_size = Vec2i::new(
+			(image.resolved_size.x >> attachment.level).max(1),
+			(image.resolved_size.y >> attachment.level).max(1),
+		);
+
+		if let Some(expected) = common_size
+			&& expected != level_size
+		{
+			return Err(GfxError::MismatchedAttachmentSizes {
+				attachment_point,
+				expected,
+				found: level_size,
+			})
+		}
 
-		assert!(common_size == None || common_size == Some(image.size));
-		common_size = Some(image.size);
+		common_size = Some(level_size);
 
-		unsafe {
-			gl::NamedFramebufferTexture(fbo.name, gl::COLOR_ATTACHMENT2, image.name, 0);
-		}
+		attach_image(fbo.name, attachment_point, image, attachment);
 	}
 
-	if let Some(handle) = def.color_attachment_3 {
-		let image = resource_manager.resolve_image(handle).unwrap();
-
-		assert!(common_size == None || common_size == Some(image.size));
-		common_size = Some(image.size);
+	// Gaps are left as GL_NONE so that fragment outputs line up with attachment points
+	let draw_buffers: Vec<u32> = def.color_attachments.iter().enumerate()
+		.map(|(index, attachment)| match attachment {
+			Some(_) => gl::COLOR_ATTACHMENT0 + index as u32,
+			None => gl::NONE,
+		})
+		.collect();
 
-		unsafe {
-			gl::NamedFramebufferTexture(fbo.name, gl::COLOR_ATTACHMENT3, image.name, 0);
+	unsafe {
+		if draw_buffers.is_empty() {
+			gl::NamedFramebufferDrawBuffer(fbo.name, gl::NONE);
+			gl::NamedFramebufferReadBuffer(fbo.name, gl::NONE);
+		} else {
+			gl::NamedFramebufferDrawBuffers(fbo.name, draw_buffers.len() as i32, draw_buffers.as_ptr());
+			gl::NamedFramebufferReadBuffer(fbo.name, def.default_read_buffer());
 		}
 	}
 
-	if let Some(handle) = def.depth_stencil_attachment {
-		let image = resource_manager.resolve_image(handle).unwrap();
+	let status = unsafe { gl::CheckNamedFramebufferStatus(fbo.name, gl::DRAW_FRAMEBUFFER) };
+	if status != gl::FRAMEBUFFER_COMPLETE {
+		return Err(GfxError::IncompleteFramebuffer { status })
+	}
 
-		assert!(common_size == None || common_size == Some(image.size));
-		common_size = Some(image.size);
+	fbo.viewport_size = common_size.unwrap_or(Vec2i::zero());
 
-		unsafe {
-			gl::NamedFramebufferTexture(fbo.name, gl::DEPTH_STENCIL_ATTACHMENT, image.name, 0);
-		}
-	}
+	Ok(())
+}
 
-	let status = unsafe { gl::CheckNamedFramebufferStatus(fbo.name, gl::DRAW_FRAMEBUFFER) };
-	assert!(status == gl::FRAMEBUFFER_COMPLETE);
+fn attach_image(fbo_name: u32, attachment_point: u32, image: &ImageObject, attachment: FboAttachment) {
+	let level = attachment.level as i32;
 
-	fbo.viewport_size = common_size
-		.map(|s| s.resolve(resource_manager.backbuffer_size))
-		.unwrap_or(Vec2i::zero());
-}
\ No newline at end of file
+	unsafe {
+		match attachment.layer {
+			Some(layer) => gl::NamedFramebufferTextureLayer(fbo_name, attachment_point, image.name, level, layer as i32),
+			None => gl::NamedFramebufferTexture(fbo_name, attachment_point, image.name, level),
+		}
+	}
+}
@@ -1,12 +1,48 @@
 use super::{ResourceManager, ResourcePath, ResourcePathRef};
-use common::math::Vec2i;
+use common::math::{Vec2i, Vec3i};
+use anyhow::Context as _;
+
+pub mod mips;
+pub mod compressed;
+pub mod async_load;
+pub mod view;
 
 #[derive(Hash, Clone, Debug, Eq, PartialEq)]
 pub enum ImageDef {
-	Path(ResourcePath),
+	Path {
+		path: ResourcePath,
+		options: ImageLoadOptions,
+	},
+
+	Cubemap {
+		source: CubemapSource,
+		options: ImageLoadOptions,
+	},
+
+	/// Every image in a directory, ordered by file name, as the layers of a 2D array texture.
+	ArrayDirectory {
+		path: ResourcePath,
+		options: ImageLoadOptions,
+	},
+
+	/// A 2D image with tightly packed texels in `format`. Never shared, so each load creates a new image.
+	Pixels {
+		format: u32,
+		size: Vec2i,
+		data: Vec<u8>,
+	},
+
 	Runtime {
 		format: u32,
 		size: ImageSize,
+		texture_type: TextureType,
+
+		/// Array layers, cubes for cube arrays, or depth for 3D textures.
+		layers: u32,
+		levels: u32,
+
+		/// Only 2D and 2D array images can be multisampled.
+		samples: u32,
 	}
 }
 
@@ -15,6 +51,10 @@ impl ImageDef {
 		ImageDef::Runtime {
 			format,
 			size: ImageSize::Backbuffer,
+			texture_type: TextureType::Tex2D,
+			layers: 1,
+			levels: 1,
+			samples: 1,
 		}
 	}
 
@@ -22,8 +62,305 @@ impl ImageDef {
 		ImageDef::render_target(gl::DEPTH24_STENCIL8)
 	}
 
+	pub fn texture_array(format: u32, size: Vec2i, layers: u32) -> ImageDef {
+		ImageDef::Runtime {
+			format,
+			size: ImageSize::Fixed(size),
+			texture_type: TextureType::Tex2DArray,
+			layers,
+			levels: 1,
+			samples: 1,
+		}
+	}
+
+	pub fn cubemap(format: u32, face_size: i32) -> ImageDef {
+		ImageDef::Runtime {
+			format,
+			size: ImageSize::Fixed(Vec2i::splat(face_size)),
+			texture_type: TextureType::Cube,
+			layers: 1,
+			levels: 1,
+			samples: 1,
+		}
+	}
+
+	pub fn cubemap_array(format: u32, face_size: i32, cubes: u32) -> ImageDef {
+		ImageDef::Runtime {
+			format,
+			size: ImageSize::Fixed(Vec2i::splat(face_size)),
+			texture_type: TextureType::CubeArray,
+			layers: cubes,
+			levels: 1,
+			samples: 1,
+		}
+	}
+
+	pub fn texture_3d(format: u32, size: Vec3i) -> ImageDef {
+		ImageDef::Runtime {
+			format,
+			size: ImageSize::Fixed(Vec2i::new(size.x, size.y)),
+			texture_type: TextureType::Tex3D,
+			layers: size.z as u32,
+			levels: 1,
+			samples: 1,
+		}
+	}
+
+	/// Sets the size of a runtime image - e.g., `ImageSize::BackbufferScaled(1, 2)` for a half resolution target.
+	pub fn size(mut self, new_size: ImageSize) -> ImageDef {
+		if let ImageDef::Runtime{ size, .. } = &mut self {
+			*size = new_size;
+		}
+
+		self
+	}
+
+	/// Sets the number of mip levels allocated for a runtime image. Loaded images take theirs from `ImageLoadOptions`.
+	/// Clamped to the full mip chain of the resolved size, so `u32::MAX` always allocates every level.
+	pub fn levels(mut self, new_levels: u32) -> ImageDef {
+		if let ImageDef::Runtime{ levels, .. } = &mut self {
+			*levels = new_levels;
+		}
+
+		self
+	}
+
+	pub fn samples(mut self, new_samples: u32) -> ImageDef {
+		if let ImageDef::Runtime{ samples, .. } = &mut self {
+			*samples = new_samples;
+		}
+
+		self
+	}
+
+	pub fn cubemap_faces<P: Into<ResourcePath>>(faces: [P; 6], options: ImageLoadOptions) -> ImageDef {
+		ImageDef::Cubemap {
+			source: CubemapSource::Faces(faces.map(Into::into)),
+			options,
+		}
+	}
+
+	pub fn cubemap_cross(path: impl Into<ResourcePath>, options: ImageLoadOptions) -> ImageDef {
+		ImageDef::Cubemap {
+			source: CubemapSource::Cross(path.into()),
+			options,
+		}
+	}
+
+	pub fn array_directory(path: impl Into<ResourcePath>, options: ImageLoadOptions) -> ImageDef {
+		ImageDef::ArrayDirectory {
+			path: path.into(),
+			options,
+		}
+	}
+
+	pub fn from_pixels<T>(format: u32, size: Vec2i, data: &[T]) -> ImageDef
+		where T: Copy
+	{
+		let byte_size = data.len() * std::mem::size_of::<T>();
+		let bytes = unsafe { std::slice::from_raw_parts(data.as_ptr().cast::<u8>(), byte_size) };
+
+		ImageDef::Pixels {
+			format,
+			size,
+			data: bytes.to_vec(),
+		}
+	}
+
 	pub fn is_shared(&self) -> bool {
-		matches!(self, ImageDef::Path(_))
+		!matches!(self, ImageDef::Runtime{..} | ImageDef::Pixels{..})
+	}
+}
+
+
+#[derive(Hash, Clone, Debug, Eq, PartialEq)]
+pub enum CubemapSource {
+	/// One image per face, in GL order: +X, -X, +Y, -Y, +Z, -Z.
+	Faces([ResourcePath; 6]),
+
+	/// A single image laid out as a horizontal (4x3) or vertical (3x4) cross.
+	Cross(ResourcePath),
+}
+
+
+#[derive(Hash, Copy, Clone, Debug, Eq, PartialEq)]
+pub enum TextureType {
+	Tex2D,
+	Tex2DArray,
+	Cube,
+	CubeArray,
+	Tex3D,
+}
+
+impl TextureType {
+	pub fn gl_target(self) -> u32 {
+		match self {
+			TextureType::Tex2D => gl::TEXTURE_2D,
+			TextureType::Tex2DArray => gl::TEXTURE_2D_ARRAY,
+			TextureType::Cube => gl::TEXTURE_CUBE_MAP,
+			TextureType::CubeArray => gl::TEXTURE_CUBE_MAP_ARRAY,
+			TextureType::Tex3D => gl::TEXTURE_3D,
+		}
+	}
+
+	pub fn gl_multisample_target(self) -> Option<u32> {
+		match self {
+			TextureType::Tex2D => Some(gl::TEXTURE_2D_MULTISAMPLE),
+			TextureType::Tex2DArray => Some(gl::TEXTURE_2D_MULTISAMPLE_ARRAY),
+			_ => None,
+		}
+	}
+
+	/// Whether the texture has more than one layer (or face, or slice) that can be individually attached or bound.
+	pub fn is_layered(self) -> bool {
+		!matches!(self, TextureType::Tex2D)
+	}
+}
+
+
+#[derive(Hash, Copy, Clone, Debug, Eq, PartialEq, serde::Deserialize)]
+pub enum ColorSpace {
+	/// Only supported for 8-bit RGB and RGBA images.
+	Srgb,
+	Linear,
+}
+
+#[derive(Hash, Copy, Clone, Debug, Eq, PartialEq, serde::Deserialize)]
+pub enum MipGeneration {
+	/// Only allocate the base level.
+	None,
+	/// Generate with GenerateTextureMipmap - fast but quality is up to the driver.
+	Gpu,
+	/// Generate on the CPU with a 2x2 box filter.
+	CpuBox,
+	/// Generate on the CPU with a Kaiser-windowed sinc filter - sharper than box.
+	CpuKaiser,
+}
+
+#[derive(Hash, Copy, Clone, Debug, Eq, PartialEq, serde::Deserialize)]
+pub enum ChannelLayout {
+	R8,
+	Rg8,
+	Rgb8,
+	Rgba8,
+	R16,
+	Rg16,
+	Rgb16,
+	Rgba16,
+}
+
+impl ChannelLayout {
+	pub fn num_channels(self) -> usize {
+		match self {
+			ChannelLayout::R8 | ChannelLayout::R16 => 1,
+			ChannelLayout::Rg8 | ChannelLayout::Rg16 => 2,
+			ChannelLayout::Rgb8 | ChannelLayout::Rgb16 => 3,
+			ChannelLayout::Rgba8 | ChannelLayout::Rgba16 => 4,
+		}
+	}
+
+	pub fn is_16_bit(self) -> bool {
+		matches!(self, ChannelLayout::R16 | ChannelLayout::Rg16 | ChannelLayout::Rgb16 | ChannelLayout::Rgba16)
+	}
+}
+
+
+#[derive(Hash, Clone, Debug, Eq, PartialEq, serde::Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ImageLoadOptions {
+	pub color_space: ColorSpace,
+	pub mips: MipGeneration,
+	pub channels: ChannelLayout,
+	pub flip_vertical: bool,
+}
+
+impl Default for ImageLoadOptions {
+	fn default() -> ImageLoadOptions {
+		ImageLoadOptions {
+			color_space: ColorSpace::Srgb,
+			mips: MipGeneration::None,
+			channels: ChannelLayout::Rgba8,
+			flip_vertical: true,
+		}
+	}
+}
+
+impl ImageLoadOptions {
+	pub fn color_space(mut self, color_space: ColorSpace) -> ImageLoadOptions {
+		self.color_space = color_space;
+		self
+	}
+
+	pub fn linear(self) -> ImageLoadOptions {
+		self.color_space(ColorSpace::Linear)
+	}
+
+	pub fn mips(mut self, mips: MipGeneration) -> ImageLoadOptions {
+		self.mips = mips;
+		self
+	}
+
+	pub fn channels(mut self, channels: ChannelLayout) -> ImageLoadOptions {
+		self.channels = channels;
+		self
+	}
+
+	pub fn flip_vertical(mut self, flip_vertical: bool) -> ImageLoadOptions {
+		self.flip_vertical = flip_vertical;
+		self
+	}
+
+	// (internal format, upload format, upload type)
+	fn gl_formats(&self) -> anyhow::Result<(u32, u32, u32)> {
+		use ChannelLayout::*;
+
+		let formats = match (self.channels, self.color_space) {
+			(Rgb8, ColorSpace::Srgb) => (gl::SRGB8, gl::RGB, gl::UNSIGNED_BYTE),
+			(Rgba8, ColorSpace::Srgb) => (gl::SRGB8_ALPHA8, gl::RGBA, gl::UNSIGNED_BYTE),
+			(channels, ColorSpace::Srgb) => anyhow::bail!("sRGB isn't supported for {channels:?} images"),
+
+			(R8, ColorSpace::Linear) => (gl::R8, gl::RED, gl::UNSIGNED_BYTE),
+			(Rg8, ColorSpace::Linear) => (gl::RG8, gl::RG, gl::UNSIGNED_BYTE),
+			(Rgb8, ColorSpace::Linear) => (gl::RGB8, gl::RGB, gl::UNSIGNED_BYTE),
+			(Rgba8, ColorSpace::Linear) => (gl::RGBA8, gl::RGBA, gl::UNSIGNED_BYTE),
+			(R16, ColorSpace::Linear) => (gl::R16, gl::RED, gl::UNSIGNED_SHORT),
+			(Rg16, ColorSpace::Linear) => (gl::RG16, gl::RG, gl::UNSIGNED_SHORT),
+			(Rgb16, ColorSpace::Linear) => (gl::RGB16, gl::RGB, gl::UNSIGNED_SHORT),
+			(Rgba16, ColorSpace::Linear) => (gl::RGBA16, gl::RGBA, gl::UNSIGNED_SHORT),
+		};
+
+		Ok(formats)
+	}
+}
+
+
+/// A rectangle within a single mip level and layer (or cubemap face) of an image.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct ImageRegion {
+	pub offset: Vec2i,
+	pub size: Vec2i,
+	pub level: u32,
+	pub layer: u32,
+}
+
+impl ImageRegion {
+	pub fn new(offset: Vec2i, size: Vec2i) -> ImageRegion {
+		ImageRegion {
+			offset,
+			size,
+			level: 0,
+			layer: 0,
+		}
+	}
+
+	pub fn level(mut self, level: u32) -> ImageRegion {
+		self.level = level;
+		self
+	}
+
+	pub fn layer(mut self, layer: u32) -> ImageRegion {
+		self.layer = layer;
+		self
 	}
 }
 
@@ -32,6 +369,10 @@ impl ImageDef {
 pub enum ImageSize {
 	Fixed(Vec2i),
 	Backbuffer,
+
+	/// The backbuffer size multiplied by `numerator / denominator`, rounded down but never less than 1.
+	/// `denominator` must not be zero.
+	BackbufferScaled(u32, u32),
 }
 
 impl ImageSize {
@@ -39,20 +380,60 @@ impl ImageSize {
 		match self {
 			ImageSize::Fixed(size) => size,
 			ImageSize::Backbuffer => backbuffer_size,
+			ImageSize::BackbufferScaled(numerator, denominator) => {
+				let scale = |x: i32| (x as i64 * numerator as i64 / denominator as i64).max(1) as i32;
+				Vec2i::new(scale(backbuffer_size.x), scale(backbuffer_size.y))
+			}
 		}
 	}
+
+	pub fn is_backbuffer_relative(self) -> bool {
+		!matches!(self, ImageSize::Fixed(_))
+	}
 }
 
 #[derive(Debug)]
 pub struct ImageObject {
 	pub name: u32,
 	pub size: ImageSize,
+	pub resolved_size: Vec2i,
 	pub format: u32,
+
+	/// Mip levels actually allocated.
+	pub levels: u32,
+
+	/// Mip levels as requested, which `levels` is clamped from - for runtime images this may exceed what `resolved_size`
+	/// can hold, and is used to reallocate levels when backbuffer relative images are resized.
+	pub requested_levels: u32,
+	pub samples: u32,
+	pub texture_type: TextureType,
+
+	/// Array layers, cubes for cube arrays, or depth for 3D textures.
+	pub layers: u32,
+}
+
+impl ImageObject {
+	/// How many layers can be individually addressed at `level` - array layers, cubemap faces or 3D slices.
+	pub fn addressable_layers(&self, level: u32) -> u32 {
+		match self.texture_type {
+			TextureType::Cube => 6,
+			TextureType::CubeArray => self.layers * 6,
+			TextureType::Tex3D => (self.layers >> level).max(1),
+			_ => self.layers,
+		}
+	}
 }
 
 impl ImageDef {
 	pub fn new(path: impl Into<ResourcePath>) -> ImageDef {
-		ImageDef::Path(path.into())
+		ImageDef::with_options(path, ImageLoadOptions::default())
+	}
+
+	pub fn with_options(path: impl Into<ResourcePath>, options: ImageLoadOptions) -> ImageDef {
+		ImageDef::Path {
+			path: path.into(),
+			options,
+		}
 	}
 }
 
@@ -63,33 +444,279 @@ pub(super) fn load(resource_manager: &ResourceManager, def: &ImageDef)
 	let backbuffer_size = resource_manager.backbuffer_size;
 
 	match def {
-		ImageDef::Path(path) => load_from_path(resource_manager, path),
-		ImageDef::Runtime{ format, size } => create_rendertarget(*format, size.resolve(backbuffer_size)),
+		ImageDef::Path{path, options} => load_from_path(resource_manager, path, options),
+		ImageDef::Cubemap{source, options} => load_cubemap(resource_manager, source, options),
+		ImageDef::ArrayDirectory{path, options} => load_array_directory(resource_manager, path, options),
+		ImageDef::Pixels{format, size, data} => create_from_pixels(*format, *size, data),
+
+		&ImageDef::Runtime{ format, size, texture_type, layers, levels, samples } => {
+			create_runtime(format, size, backbuffer_size, texture_type, layers, levels, samples)
+		}
 	}
 }
 
 
-fn load_from_path(resource_manager: &ResourceManager, path: &ResourcePathRef)
+/// (format, type, bytes per texel) for transferring tightly packed texels of `internal_format` to or from a buffer.
+pub fn pixel_transfer_format(internal_format: u32) -> Option<(u32, u32, usize)> {
+	let formats = match internal_format {
+		gl::R8 => (gl::RED, gl::UNSIGNED_BYTE, 1),
+		gl::RG8 => (gl::RG, gl::UNSIGNED_BYTE, 2),
+		gl::RGB8 | gl::SRGB8 => (gl::RGB, gl::UNSIGNED_BYTE, 3),
+		gl::RGBA8 | gl::SRGB8_ALPHA8 => (gl::RGBA, gl::UNSIGNED_BYTE, 4),
+
+		gl::R16 => (gl::RED, gl::UNSIGNED_SHORT, 2),
+		gl::RG16 => (gl::RG, gl::UNSIGNED_SHORT, 4),
+		gl::RGB16 => (gl::RGB, gl::UNSIGNED_SHORT, 6),
+		gl::RGBA16 => (gl::RGBA, gl::UNSIGNED_SHORT, 8),
+
+		gl::R16F => (gl::RED, gl::HALF_FLOAT, 2),
+		gl::RG16F => (gl::RG, gl::HALF_FLOAT, 4),
+		gl::RGBA16F => (gl::RGBA, gl::HALF_FLOAT, 8),
+		gl::R32F => (gl::RED, gl::FLOAT, 4),
+		gl::RG32F => (gl::RG, gl::FLOAT, 8),
+		gl::RGB32F => (gl::RGB, gl::FLOAT, 12),
+		gl::RGBA32F => (gl::RGBA, gl::FLOAT, 16),
+		gl::R11F_G11F_B10F => (gl::RGB, gl::UNSIGNED_INT_10F_11F_11F_REV, 4),
+
+		gl::R8UI => (gl::RED_INTEGER, gl::UNSIGNED_BYTE, 1),
+		gl::R32UI => (gl::RED_INTEGER, gl::UNSIGNED_INT, 4),
+		gl::RG32UI => (gl::RG_INTEGER, gl::UNSIGNED_INT, 8),
+		gl::RGBA8UI => (gl::RGBA_INTEGER, gl::UNSIGNED_BYTE, 4),
+		gl::RGBA32UI => (gl::RGBA_INTEGER, gl::UNSIGNED_INT, 16),
+
+		gl::DEPTH_COMPONENT32F => (gl::DEPTH_COMPONENT, gl::FLOAT, 4),
+		gl::DEPTH24_STENCIL8 => (gl::DEPTH_STENCIL, gl::UNSIGNED_INT_24_8, 4),
+
+		_ => return None,
+	};
+
+	Some(formats)
+}
+
+
+/// Allocates immutable storage for a new texture. `layers` is ignored for 2D and cube textures,
+/// and `levels` is clamped to the full mip chain for `size`.
+pub(super) fn create_texture(texture_type: TextureType, format: u32, size: Vec2i, layers: u32, levels: u32, samples: u32) -> u32 {
+	let mut name = 0;
+
+	if samples > 1 {
+		let target = texture_type.gl_multisample_target()
+			.expect("Only 2D and 2D array images can be multisampled");
+
+		unsafe {
+			gl::CreateTextures(target, 1, &mut name);
+
+			match texture_type {
+				TextureType::Tex2D => gl::TextureStorage2DMultisample(name, samples as i32, format, size.x, size.y, gl::TRUE),
+				_ => gl::TextureStorage3DMultisample(name, samples as i32, format, size.x, size.y, layers as i32, gl::TRUE),
+			}
+		}
+
+		return name
+	}
+
+	let levels = levels.min(mips::mip_count(size)) as i32;
+
+	unsafe {
+		gl::CreateTextures(texture_type.gl_target(), 1, &mut name);
+
+		match texture_type {
+			TextureType::Tex2D | TextureType::Cube => gl::TextureStorage2D(name, levels, format, size.x, size.y),
+			TextureType::Tex2DArray | TextureType::Tex3D => gl::TextureStorage3D(name, levels, format, size.x, size.y, layers as i32),
+			TextureType::CubeArray => gl::TextureStorage3D(name, levels, format, size.x, size.y, layers as i32 * 6),
+		}
+	}
+
+	name
+}
+
+
+fn load_from_path(resource_manager: &ResourceManager, path: &ResourcePathRef, options: &ImageLoadOptions)
 	-> anyhow::Result<ImageObject>
 {
-	let image = image::open(&resource_manager.resolve_path(path))?.flipv().into_rgba8().into_flat_samples();
-	let size = Vec2i::new(image.layout.width as i32, image.layout.height as i32);
-	let Vec2i{x: width, y: height} = size;
+	if compressed::is_compressed_container(path) {
+		return load_compressed(resource_manager, path);
+	}
 
-	let mut name = 0;
+	let image = open_image(resource_manager, path)?;
+	create_texture_from_images(path, TextureType::Tex2D, vec![to_pixel_data(image, options)], options)
+}
+
+fn load_cubemap(resource_manager: &ResourceManager, source: &CubemapSource, options: &ImageLoadOptions)
+	-> anyhow::Result<ImageObject>
+{
+	let (label_path, faces) = match source {
+		CubemapSource::Faces(paths) => {
+			let faces = paths.iter()
+				.map(|path| open_image(resource_manager, path))
+				.collect::<anyhow::Result<Vec<_>>>()?;
+
+			(&paths[0], faces)
+		}
+
+		CubemapSource::Cross(path) => {
+			let image = open_image(resource_manager, path)?;
+			let faces = split_cross(image)
+				.with_context(|| format!("Failed to split cubemap cross '{}'", path.display()))?;
+
+			(path, faces)
+		}
+	};
+
+	let faces = faces.into_iter()
+		.map(|face| to_pixel_data(face, options))
+		.collect();
+
+	create_texture_from_images(label_path, TextureType::Cube, faces, options)
+}
+
+fn load_array_directory(resource_manager: &ResourceManager, path: &ResourcePathRef, options: &ImageLoadOptions)
+	-> anyhow::Result<ImageObject>
+{
+	let mut files = resource_manager.list_directory(path);
+
+	files.retain(|file| image::ImageFormat::from_path(file).is_ok());
+	files.sort();
+
+	anyhow::ensure!(!files.is_empty(), "Image array directory '{}' contains no images", path.display());
+
+	let layers = files.iter()
+		.map(|file| open_image(resource_manager, file).map(|image| to_pixel_data(image, options)))
+		.collect::<anyhow::Result<Vec<_>>>()?;
+
+	create_texture_from_images(path, TextureType::Tex2DArray, layers, options)
+}
+
+
+fn open_image(resource_manager: &ResourceManager, path: &ResourcePathRef) -> anyhow::Result<image::DynamicImage> {
+	let data = resource_manager.load_binary(path)?;
+	decode_image(path, &data)
+}
+
+fn decode_image(path: &ResourcePathRef, data: &[u8]) -> anyhow::Result<image::DynamicImage> {
+	image::load_from_memory(data)
+		.with_context(|| format!("Failed to load image '{}'", path.display()))
+}
+
+/// Applies flipping and converts to the requested layout - keeping the first num_channels channels of RGBA.
+fn to_pixel_data(mut image: image::DynamicImage, options: &ImageLoadOptions) -> (Vec2i, mips::PixelData) {
+	if options.flip_vertical {
+		image = image.flipv();
+	}
+
+	let size = Vec2i::new(image.width() as i32, image.height() as i32);
+	let num_channels = options.channels.num_channels();
+
+	let data = match options.channels.is_16_bit() {
+		false => mips::PixelData::U8(select_channels(&image.into_rgba8().into_raw(), num_channels)),
+		true => mips::PixelData::U16(select_channels(&image.into_rgba16().into_raw(), num_channels)),
+	};
+
+	(size, data)
+}
+
+/// Splits a cross layout into faces in GL order. The -Z face of a vertical cross is stored upside down.
+fn split_cross(image: image::DynamicImage) -> anyhow::Result<Vec<image::DynamicImage>> {
+	let (width, height) = (image.width(), image.height());
+
+	// (column, row, rotated) for +X, -X, +Y, -Y, +Z, -Z
+	let (face_size, layout) = if width * 3 == height * 4 {
+		(width / 4, [(2, 1, false), (0, 1, false), (1, 0, false), (1, 2, false), (1, 1, false), (3, 1, false)])
+	} else if width * 4 == height * 3 {
+		(width / 3, [(2, 1, false), (0, 1, false), (1, 0, false), (1, 2, false), (1, 1, false), (1, 3, true)])
+	} else {
+		anyhow::bail!("{width}x{height} isn't a 4x3 or 3x4 cross layout");
+	};
+
+	let faces = layout.into_iter()
+		.map(|(column, row, rotated)| {
+			let face = image.crop_imm(column * face_size, row * face_size, face_size, face_size);
+			match rotated {
+				true => face.rotate180(),
+				false => face,
+			}
+		})
+		.collect();
+
+	Ok(faces)
+}
+
+/// Creates a texture from decoded images - one per array layer, or one per face for cubemaps.
+fn create_texture_from_images(path: &ResourcePathRef, texture_type: TextureType, images: Vec<(Vec2i, mips::PixelData)>,
+	options: &ImageLoadOptions) -> anyhow::Result<ImageObject>
+{
+	let (internal_format, upload_format, upload_type) = options.gl_formats()?;
+
+	let size = images[0].0;
+
+	if let Some((mismatched_size, _)) = images.iter().find(|(layer_size, _)| *layer_size != size) {
+		anyhow::bail!("Every layer of '{}' must be the same size - expected {size:?}, found {mismatched_size:?}", path.display());
+	}
+
+	if texture_type == TextureType::Cube {
+		anyhow::ensure!(size.x == size.y, "Cubemap faces of '{}' must be square, found {size:?}", path.display());
+	}
+
+	let levels = match options.mips {
+		MipGeneration::None => 1,
+		_ => mips::mip_count(size),
+	};
+
+	let layers = match texture_type {
+		TextureType::Cube => 1,
+		_ => images.len() as u32,
+	};
+
+	let name = create_texture(texture_type, internal_format, size, layers, levels, 1);
 
 	unsafe {
-		gl::CreateTextures(gl::TEXTURE_2D, 1, &mut name);
-		gl::TextureStorage2D(name, 1, gl::SRGB8_ALPHA8, width, height);
+		// Rows of RGB or R8 images aren't necessarily 4 byte aligned
+		gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+	}
+
+	for (layer, (_, base_level)) in images.into_iter().enumerate() {
+		let cpu_levels = match options.mips {
+			MipGeneration::CpuBox | MipGeneration::CpuKaiser => {
+				let filter = match options.mips {
+					MipGeneration::CpuKaiser => mips::MipFilter::Kaiser,
+					_ => mips::MipFilter::Box,
+				};
+
+				let is_srgb = options.color_space == ColorSpace::Srgb;
+				mips::generate_mips(&base_level, size, options.channels.num_channels(), is_srgb, filter, levels)
+			}
+
+			_ => vec![(size, base_level)],
+		};
+
+		for (level, (level_size, data)) in cpu_levels.iter().enumerate() {
+			let (offset_x, offset_y) = (0, 0);
+
+			unsafe {
+				match texture_type {
+					TextureType::Tex2D => gl::TextureSubImage2D(name, level as i32, offset_x, offset_y,
+						level_size.x, level_size.y,
+						upload_format,
+						upload_type,
+						data.as_ptr()),
 
-		let (level, offset_x, offset_y) = (0, 0, 0);
-		let data = image.samples.as_ptr();
+					// Cubemap faces are addressed as layers through DSA
+					_ => gl::TextureSubImage3D(name, level as i32, offset_x, offset_y, layer as i32,
+						level_size.x, level_size.y, 1,
+						upload_format,
+						upload_type,
+						data.as_ptr()),
+				}
+			}
+		}
+	}
 
-		gl::TextureSubImage2D(name, level, offset_x, offset_y,
-			width, height,
-			gl::RGBA,
-			gl::UNSIGNED_BYTE,
-			data as *const _);
+	unsafe {
+		gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+
+		if options.mips == MipGeneration::Gpu {
+			gl::GenerateTextureMipmap(name);
+		}
 
 		if let Some(path_str) = path.to_str() {
 			gl::ObjectLabel(gl::TEXTURE, name, path_str.len() as i32, path_str.as_ptr() as *const _);
@@ -99,28 +726,162 @@ fn load_from_path(resource_manager: &ResourceManager, path: &ResourcePathRef)
 	Ok(ImageObject {
 		name,
 		size: ImageSize::Fixed(size),
-		format: gl::SRGB8_ALPHA8,
+		resolved_size: size,
+		format: internal_format,
+		levels,
+		requested_levels: levels,
+		samples: 1,
+		texture_type,
+		layers,
 	})
 }
 
+/// KTX2 and DDS files carry their own format, orientation and mip chain, so `ImageLoadOptions` don't apply.
+fn load_compressed(resource_manager: &ResourceManager, path: &ResourcePathRef)
+	-> anyhow::Result<ImageObject>
+{
+	let data = resource_manager.load_binary(path)?;
+	let image = compressed::load(path, &data)?;
+
+	compressed::check_format_support(image.format)?;
+
+	let texture_type = image.texture_type();
+	let format = image.format.gl_format();
+	let levels = image.levels.len() as u32;
+	let depth = (image.layers * image.faces) as i32;
 
+	let name = create_texture(texture_type, format, image.size, image.layers, levels, 1);
 
-fn create_rendertarget(format: u32, size: Vec2i)
+	unsafe {
+		for (level, data) in image.levels.iter().enumerate() {
+			let level_size = Vec2i::new((image.size.x >> level).max(1), (image.size.y >> level).max(1));
+			let (offset_x, offset_y, offset_z) = (0, 0, 0);
+
+			match texture_type {
+				TextureType::Tex2D => gl::CompressedTextureSubImage2D(name, level as i32, offset_x, offset_y,
+					level_size.x, level_size.y,
+					format,
+					data.len() as i32,
+					data.as_ptr() as *const _),
+
+				// Cubemap faces are addressed as layers through DSA
+				_ => gl::CompressedTextureSubImage3D(name, level as i32, offset_x, offset_y, offset_z,
+					level_size.x, level_size.y, depth,
+					format,
+					data.len() as i32,
+					data.as_ptr() as *const _),
+			}
+		}
+
+		if let Some(path_str) = path.to_str() {
+			gl::ObjectLabel(gl::TEXTURE, name, path_str.len() as i32, path_str.as_ptr() as *const _);
+		}
+	}
+
+	Ok(ImageObject {
+		name,
+		size: ImageSize::Fixed(image.size),
+		resolved_size: image.size,
+		format,
+		levels,
+		requested_levels: levels,
+		samples: 1,
+		texture_type,
+		layers: image.layers,
+	})
+}
+
+/// Stands in for images that are still loading.
+pub(super) fn create_placeholder() -> anyhow::Result<ImageObject> {
+	let grey = [128u8, 128, 128, 255];
+	create_from_pixels(gl::RGBA8, Vec2i::splat(1), &grey)
+}
+
+fn create_from_pixels(format: u32, size: Vec2i, data: &[u8])
 	-> anyhow::Result<ImageObject>
 {
-	let mut name = 0;
+	let Some((upload_format, upload_type, texel_size)) = pixel_transfer_format(format) else {
+		anyhow::bail!("Can't create images with format {format:#x} from pixels");
+	};
+
+	let expected_size = size.x as usize * size.y as usize * texel_size;
+	anyhow::ensure!(data.len() == expected_size, "Expected {expected_size} bytes of pixel data for {size:?} image, got {}", data.len());
+
+	let name = create_texture(TextureType::Tex2D, format, size, 1, 1, 1);
 
 	unsafe {
-		gl::CreateTextures(gl::TEXTURE_2D, 1, &mut name);
-		gl::TextureStorage2D(name, 1, format, size.x, size.y);
+		gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+
+		let (offset_x, offset_y) = (0, 0);
+		gl::TextureSubImage2D(name, 0, offset_x, offset_y, size.x, size.y, upload_format, upload_type, data.as_ptr() as *const _);
+
+		gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+
+		let label = format!("pixels {name}");
+		gl::ObjectLabel(gl::TEXTURE, name, label.len() as i32, label.as_ptr() as *const _);
+	}
+
+	Ok(ImageObject {
+		name,
+		size: ImageSize::Fixed(size),
+		resolved_size: size,
+		format,
+		levels: 1,
+		requested_levels: 1,
+		samples: 1,
+		texture_type: TextureType::Tex2D,
+		layers: 1,
+	})
+}
+
+fn select_channels<T: Copy>(rgba: &[T], num_channels: usize) -> Vec<T> {
+	if num_channels == 4 {
+		return rgba.to_vec();
+	}
+
+	rgba.chunks_exact(4)
+		.flat_map(|texel| texel[..num_channels].iter().copied())
+		.collect()
+}
+
+
+
+fn create_runtime(format: u32, size: ImageSize, backbuffer_size: Vec2i, texture_type: TextureType, layers: u32, levels: u32, samples: u32)
+	-> anyhow::Result<ImageObject>
+{
+	if let ImageSize::BackbufferScaled(numerator, denominator) = size {
+		anyhow::ensure!(denominator != 0, "Image scaled to {numerator}/{denominator} of the backbuffer - denominator can't be zero");
+	}
 
+	let resolved_size = size.resolve(backbuffer_size);
+
+	if matches!(texture_type, TextureType::Cube | TextureType::CubeArray) {
+		anyhow::ensure!(resolved_size.x == resolved_size.y, "Cubemap faces must be square, found {resolved_size:?}");
+	}
+
+	if samples > 1 {
+		anyhow::ensure!(texture_type.gl_multisample_target().is_some(), "{texture_type:?} images can't be multisampled");
+		anyhow::ensure!(levels == 1, "Multisampled images can't have mips");
+	}
+
+	anyhow::ensure!(samples > 0 && levels > 0, "Images need at least one sample and one mip level");
+
+	let name = create_texture(texture_type, format, resolved_size, layers, levels, samples);
+
+	unsafe {
 		let label = format!("rendertarget {name}");
 		gl::ObjectLabel(gl::TEXTURE, name, label.len() as i32, label.as_ptr() as *const _);
 	}
 
 	Ok(ImageObject {
 		name,
-		size: ImageSize::Backbuffer,
+		size,
+		resolved_size,
 		format,
+		levels: levels.min(mips::mip_count(resolved_size)),
+		requested_levels: levels,
+		samples,
+		texture_type,
+		layers,
 	})
 }
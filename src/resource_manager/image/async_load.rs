@@ -0,0 +1,342 @@
+use super::{ImageLoadOptions, ImageObject, ImageSize, TextureType, MipGeneration, ColorSpace};
+use super::{mips, create_texture, decode_image, to_pixel_data};
+use crate::resource_manager::{ImageHandle, ResourcePath};
+use crate::upload_heap::{UploadHeap, UPLOAD_BUFFER_SIZE};
+use common::math::Vec2i;
+use std::collections::HashMap;
+use std::sync::mpsc;
+
+
+// Streaming goes through the loader's own staging heap so that it never eats into the frame's upload heap.
+// Only half of it is used each frame so that we rarely have to wait on the previous frame's fence.
+// TODO(pat.m): the staging heap is tiny, so large images take a lot of frames to stream in.
+pub const UPLOAD_BUDGET_PER_FRAME: usize = UPLOAD_BUFFER_SIZE / 2;
+
+
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ImageLoadState {
+	/// The image resolves to a placeholder until loading finishes.
+	Loading,
+	Loaded,
+
+	/// The image will stay a placeholder.
+	Failed(String),
+}
+
+
+/// Decodes images on a worker thread, then uploads them through the upload heap a few rows at a time.
+#[derive(Debug)]
+pub struct AsyncImageLoader {
+	requests: mpsc::Sender<DecodeRequest>,
+	results: mpsc::Receiver<DecodeResult>,
+	pending: HashMap<ImageHandle, PendingImage>,
+	staging_heap: UploadHeap,
+}
+
+impl AsyncImageLoader {
+	pub fn new() -> AsyncImageLoader {
+		let (requests, worker_requests) = mpsc::channel();
+		let (worker_results, results) = mpsc::channel();
+
+		// The worker exits once the loader is dropped and the request channel closes
+		std::thread::Builder::new()
+			.name("image decoder".into())
+			.spawn(move || decode_worker(worker_requests, worker_results))
+			.expect("Failed to spawn image decoder thread");
+
+		AsyncImageLoader {
+			requests,
+			results,
+			pending: HashMap::new(),
+			staging_heap: UploadHeap::new(),
+		}
+	}
+
+	/// `path` is only used for labelling and error messages - `data` is the encoded image.
+	pub fn request(&mut self, handle: ImageHandle, path: ResourcePath, data: Vec<u8>, options: ImageLoadOptions) {
+		let label = path.display().to_string();
+
+		self.pending.insert(handle, PendingImage::Decoding { options: options.clone(), label });
+		self.requests.send(DecodeRequest { handle, path, data, options })
+			.expect("Image decoder thread has gone away");
+	}
+
+	/// None if `handle` isn't an image this loader knows about, which includes images that have finished loading.
+	pub fn state(&self, handle: ImageHandle) -> Option<ImageLoadState> {
+		let state = match self.pending.get(&handle)? {
+			PendingImage::Decoding{..} | PendingImage::Uploading(_) => ImageLoadState::Loading,
+			PendingImage::Failed(error) => ImageLoadState::Failed(error.clone()),
+		};
+
+		Some(state)
+	}
+
+	/// Stops tracking `handle`. Returns the name of any partially uploaded texture, which the caller should destroy.
+	pub fn cancel(&mut self, handle: ImageHandle) -> Option<u32> {
+		match self.pending.remove(&handle)? {
+			PendingImage::Uploading(upload) => Some(upload.name),
+			_ => None,
+		}
+	}
+
+	/// Starts uploading newly decoded images and continues any in progress, within UPLOAD_BUDGET_PER_FRAME.
+	/// Returns every image that finished uploading, to replace its placeholder.
+	pub fn process(&mut self) -> Vec<(ImageHandle, ImageObject)> {
+		while let Ok(DecodeResult{handle, levels}) = self.results.try_recv() {
+			// The image may have been destroyed while decoding
+			let Some(PendingImage::Decoding{options, label}) = self.pending.remove(&handle) else {
+				continue
+			};
+
+			let state = match levels.and_then(|levels| PendingUpload::new(levels, &options, label)) {
+				Ok(upload) => PendingImage::Uploading(upload),
+				Err(error) => PendingImage::Failed(format!("{error:#}")),
+			};
+
+			self.pending.insert(handle, state);
+		}
+
+		let mut budget = UPLOAD_BUDGET_PER_FRAME;
+		let mut finished_handles = Vec::new();
+
+		let upload_heap = &mut self.staging_heap;
+		upload_heap.reset();
+
+		unsafe {
+			gl::BindBuffer(gl::PIXEL_UNPACK_BUFFER, upload_heap.buffer_name());
+			gl::PixelStorei(gl::UNPACK_ALIGNMENT, 1);
+		}
+
+		for (&handle, pending) in self.pending.iter_mut() {
+			let PendingImage::Uploading(upload) = pending else { continue };
+
+			budget = upload.upload_rows(upload_heap, budget);
+
+			if upload.is_complete() {
+				finished_handles.push(handle);
+			}
+
+			if budget == 0 {
+				break
+			}
+		}
+
+		unsafe {
+			gl::PixelStorei(gl::UNPACK_ALIGNMENT, 4);
+			gl::BindBuffer(gl::PIXEL_UNPACK_BUFFER, 0);
+		}
+
+		// Fence this frame's uploads so the staging space isn't overwritten before they've been consumed
+		upload_heap.notify_finished();
+
+		finished_handles.into_iter()
+			.map(|handle| match self.pending.remove(&handle) {
+				Some(PendingImage::Uploading(upload)) => (handle, upload.finish()),
+				_ => unreachable!(),
+			})
+			.collect()
+	}
+}
+
+impl Drop for AsyncImageLoader {
+	fn drop(&mut self) {
+		for pending in self.pending.values() {
+			if let PendingImage::Uploading(upload) = pending {
+				unsafe {
+					gl::DeleteTextures(1, &upload.name);
+				}
+			}
+		}
+	}
+}
+
+
+
+#[derive(Debug)]
+enum PendingImage {
+	Decoding {
+		options: ImageLoadOptions,
+		label: String,
+	},
+
+	Uploading(PendingUpload),
+	Failed(String),
+}
+
+#[derive(Debug)]
+struct DecodeRequest {
+	handle: ImageHandle,
+	path: ResourcePath,
+	data: Vec<u8>,
+	options: ImageLoadOptions,
+}
+
+#[derive(Debug)]
+struct DecodeResult {
+	handle: ImageHandle,
+	levels: anyhow::Result<Vec<(Vec2i, mips::PixelData)>>,
+}
+
+fn decode_worker(requests: mpsc::Receiver<DecodeRequest>, results: mpsc::Sender<DecodeResult>) {
+	for DecodeRequest{handle, path, data, options} in requests {
+		let levels = decode(&path, &data, &options);
+
+		if results.send(DecodeResult{handle, levels}).is_err() {
+			break
+		}
+	}
+}
+
+fn decode(path: &ResourcePath, data: &[u8], options: &ImageLoadOptions) -> anyhow::Result<Vec<(Vec2i, mips::PixelData)>> {
+	// Catch unsupported options before doing any work
+	options.gl_formats()?;
+
+	let (size, base_level) = to_pixel_data(decode_image(path, data)?, options);
+
+	let levels = match options.mips {
+		MipGeneration::CpuBox | MipGeneration::CpuKaiser => {
+			let filter = match options.mips {
+				MipGeneration::CpuKaiser => mips::MipFilter::Kaiser,
+				_ => mips::MipFilter::Box,
+			};
+
+			let is_srgb = options.color_space == ColorSpace::Srgb;
+			mips::generate_mips(&base_level, size, options.channels.num_channels(), is_srgb, filter, mips::mip_count(size))
+		}
+
+		_ => vec![(size, base_level)],
+	};
+
+	Ok(levels)
+}
+
+
+
+#[derive(Debug)]
+struct PendingUpload {
+	name: u32,
+	label: String,
+
+	internal_format: u32,
+	upload_format: u32,
+	upload_type: u32,
+	generate_gpu_mips: bool,
+
+	num_levels: u32,
+	levels: Vec<(Vec2i, mips::PixelData)>,
+
+	current_level: usize,
+	current_row: i32,
+}
+
+impl PendingUpload {
+	fn new(levels: Vec<(Vec2i, mips::PixelData)>, options: &ImageLoadOptions, label: String) -> anyhow::Result<PendingUpload> {
+		let (internal_format, upload_format, upload_type) = options.gl_formats()?;
+		let size = levels[0].0;
+
+		let num_levels = match options.mips {
+			MipGeneration::None => 1,
+			_ => mips::mip_count(size),
+		};
+
+		Ok(PendingUpload {
+			name: create_texture(TextureType::Tex2D, internal_format, size, 1, num_levels, 1),
+			label,
+
+			internal_format,
+			upload_format,
+			upload_type,
+			generate_gpu_mips: options.mips == MipGeneration::Gpu,
+
+			num_levels,
+			levels,
+
+			current_level: 0,
+			current_row: 0,
+		})
+	}
+
+	fn is_complete(&self) -> bool {
+		self.current_level >= self.levels.len()
+	}
+
+	/// Expects the upload heap to be bound as the pixel unpack buffer. Returns the remaining budget.
+	fn upload_rows(&mut self, upload_heap: &mut UploadHeap, mut budget: usize) -> usize {
+		while let Some((level_size, data)) = self.levels.get(self.current_level) {
+			let level_size = *level_size;
+			let bytes = data.as_bytes();
+			let row_size = bytes.len() / level_size.y as usize;
+
+			if row_size > UPLOAD_BUDGET_PER_FRAME {
+				// Rows that could never fit in the budget go straight from client memory instead
+				unsafe {
+					gl::BindBuffer(gl::PIXEL_UNPACK_BUFFER, 0);
+					self.upload_region(level_size, 0, level_size.y, bytes.as_ptr().cast());
+					gl::BindBuffer(gl::PIXEL_UNPACK_BUFFER, upload_heap.buffer_name());
+				}
+
+				self.current_level += 1;
+				self.current_row = 0;
+				continue
+			}
+
+			let num_rows = ((budget / row_size) as i32).min(level_size.y - self.current_row);
+			if num_rows == 0 {
+				break
+			}
+
+			let start = self.current_row as usize * row_size;
+			let end = start + num_rows as usize * row_size;
+			let allocation = upload_heap.push_data(&bytes[start..end], 4);
+
+			// With a bound unpack buffer the data pointer is an offset into it
+			unsafe {
+				self.upload_region(level_size, self.current_row, num_rows, allocation.offset as *const _);
+			}
+
+			budget -= end - start;
+			self.current_row += num_rows;
+
+			if self.current_row == level_size.y {
+				self.current_level += 1;
+				self.current_row = 0;
+			}
+		}
+
+		budget
+	}
+
+	unsafe fn upload_region(&self, level_size: Vec2i, first_row: i32, num_rows: i32, data: *const std::ffi::c_void) {
+		let offset_x = 0;
+
+		gl::TextureSubImage2D(self.name, self.current_level as i32, offset_x, first_row,
+			level_size.x, num_rows,
+			self.upload_format,
+			self.upload_type,
+			data);
+	}
+
+	fn finish(self) -> ImageObject {
+		let size = self.levels[0].0;
+
+		unsafe {
+			if self.generate_gpu_mips {
+				gl::GenerateTextureMipmap(self.name);
+			}
+
+			gl::ObjectLabel(gl::TEXTURE, self.name, self.label.len() as i32, self.label.as_ptr() as *const _);
+		}
+
+		ImageObject {
+			name: self.name,
+			size: ImageSize::Fixed(size),
+			resolved_size: size,
+			format: self.internal_format,
+			levels: self.num_levels,
+			requested_levels: self.num_levels,
+			samples: 1,
+			texture_type: TextureType::Tex2D,
+			layers: 1,
+		}
+	}
+}
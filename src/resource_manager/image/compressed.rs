@@ -0,0 +1,418 @@
+use super::{TextureType, mips};
+use common::math::Vec2i;
+use anyhow::Context as _;
+
+
+/// A block compressed image loaded from a KTX2 or DDS container.
+/// `levels` is ordered from the base level down, and each level holds every layer and face packed together
+/// in the order GL expects for array and cube textures - layer major, then face.
+#[derive(Debug)]
+pub struct CompressedImage {
+	pub format: CompressedFormat,
+	pub size: Vec2i,
+	pub layers: u32,
+	pub faces: u32,
+	pub levels: Vec<Vec<u8>>,
+}
+
+impl CompressedImage {
+	pub fn texture_type(&self) -> TextureType {
+		match (self.faces, self.layers) {
+			(6, 1) => TextureType::Cube,
+			(6, _) => TextureType::CubeArray,
+			(_, 1) => TextureType::Tex2D,
+			_ => TextureType::Tex2DArray,
+		}
+	}
+}
+
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum CompressedFormat {
+	Bc1Rgb,
+	Bc1RgbSrgb,
+	Bc1Rgba,
+	Bc1RgbaSrgb,
+	Bc2,
+	Bc2Srgb,
+	Bc3,
+	Bc3Srgb,
+	Bc4Unorm,
+	Bc4Snorm,
+	Bc5Unorm,
+	Bc5Snorm,
+	Bc6hUfloat,
+	Bc6hSfloat,
+	Bc7,
+	Bc7Srgb,
+	Etc2Rgb,
+	Etc2RgbSrgb,
+	Etc2RgbA1,
+	Etc2RgbA1Srgb,
+	Etc2Rgba,
+	Etc2RgbaSrgb,
+}
+
+impl CompressedFormat {
+	pub fn gl_format(self) -> u32 {
+		use CompressedFormat::*;
+
+		match self {
+			Bc1Rgb => gl::COMPRESSED_RGB_S3TC_DXT1_EXT,
+			Bc1RgbSrgb => gl::COMPRESSED_SRGB_S3TC_DXT1_EXT,
+			Bc1Rgba => gl::COMPRESSED_RGBA_S3TC_DXT1_EXT,
+			Bc1RgbaSrgb => gl::COMPRESSED_SRGB_ALPHA_S3TC_DXT1_EXT,
+			Bc2 => gl::COMPRESSED_RGBA_S3TC_DXT3_EXT,
+			Bc2Srgb => gl::COMPRESSED_SRGB_ALPHA_S3TC_DXT3_EXT,
+			Bc3 => gl::COMPRESSED_RGBA_S3TC_DXT5_EXT,
+			Bc3Srgb => gl::COMPRESSED_SRGB_ALPHA_S3TC_DXT5_EXT,
+			Bc4Unorm => gl::COMPRESSED_RED_RGTC1,
+			Bc4Snorm => gl::COMPRESSED_SIGNED_RED_RGTC1,
+			Bc5Unorm => gl::COMPRESSED_RG_RGTC2,
+			Bc5Snorm => gl::COMPRESSED_SIGNED_RG_RGTC2,
+			Bc6hUfloat => gl::COMPRESSED_RGB_BPTC_UNSIGNED_FLOAT,
+			Bc6hSfloat => gl::COMPRESSED_RGB_BPTC_SIGNED_FLOAT,
+			Bc7 => gl::COMPRESSED_RGBA_BPTC_UNORM,
+			Bc7Srgb => gl::COMPRESSED_SRGB_ALPHA_BPTC_UNORM,
+			Etc2Rgb => gl::COMPRESSED_RGB8_ETC2,
+			Etc2RgbSrgb => gl::COMPRESSED_SRGB8_ETC2,
+			Etc2RgbA1 => gl::COMPRESSED_RGB8_PUNCHTHROUGH_ALPHA1_ETC2,
+			Etc2RgbA1Srgb => gl::COMPRESSED_SRGB8_PUNCHTHROUGH_ALPHA1_ETC2,
+			Etc2Rgba => gl::COMPRESSED_RGBA8_ETC2_EAC,
+			Etc2RgbaSrgb => gl::COMPRESSED_SRGB8_ALPHA8_ETC2_EAC,
+		}
+	}
+
+	/// Bytes per 4x4 block.
+	pub fn block_size(self) -> usize {
+		use CompressedFormat::*;
+
+		match self {
+			Bc1Rgb | Bc1RgbSrgb | Bc1Rgba | Bc1RgbaSrgb
+			| Bc4Unorm | Bc4Snorm
+			| Etc2Rgb | Etc2RgbSrgb | Etc2RgbA1 | Etc2RgbA1Srgb => 8,
+
+			_ => 16,
+		}
+	}
+
+	/// Extensions that must be advertised for GL to accept this format.
+	/// RGTC, BPTC and ETC2 are core in 4.6, S3TC never made it into core.
+	pub fn required_extensions(self) -> &'static [&'static str] {
+		use CompressedFormat::*;
+
+		match self {
+			Bc1Rgb | Bc1Rgba | Bc2 | Bc3 => &["GL_EXT_texture_compression_s3tc"],
+			Bc1RgbSrgb | Bc1RgbaSrgb | Bc2Srgb | Bc3Srgb => &["GL_EXT_texture_compression_s3tc", "GL_EXT_texture_sRGB"],
+			_ => &[],
+		}
+	}
+
+	pub fn level_byte_size(self, size: Vec2i) -> usize {
+		let blocks_x = (size.x as usize + 3) / 4;
+		let blocks_y = (size.y as usize + 3) / 4;
+		blocks_x * blocks_y * self.block_size()
+	}
+}
+
+
+pub fn is_compressed_container(path: &std::path::Path) -> bool {
+	path.extension()
+		.and_then(|ext| ext.to_str())
+		.map_or(false, |ext| ext.eq_ignore_ascii_case("ktx2") || ext.eq_ignore_ascii_case("dds"))
+}
+
+pub fn load(path: &std::path::Path, data: &[u8]) -> anyhow::Result<CompressedImage> {
+	let is_ktx2 = path.extension()
+		.and_then(|ext| ext.to_str())
+		.map_or(false, |ext| ext.eq_ignore_ascii_case("ktx2"));
+
+	let image = match is_ktx2 {
+		true => parse_ktx2(data).context("Failed to parse KTX2 container")?,
+		false => parse_dds(data).context("Failed to parse DDS container")?,
+	};
+
+	anyhow::ensure!(!image.levels.is_empty(), "Container has no mip levels");
+
+	Ok(image)
+}
+
+/// Fails if the current context is missing any extension needed to upload `format`.
+pub fn check_format_support(format: CompressedFormat) -> anyhow::Result<()> {
+	let required = format.required_extensions();
+	if required.is_empty() {
+		return Ok(())
+	}
+
+	let supported = supported_extensions();
+
+	for extension in required {
+		anyhow::ensure!(supported.iter().any(|supported| supported == extension),
+			"{format:?} textures require {extension}, which isn't supported by this GL implementation");
+	}
+
+	Ok(())
+}
+
+fn supported_extensions() -> Vec<String> {
+	let mut num_extensions = 0;
+
+	unsafe {
+		gl::GetIntegerv(gl::NUM_EXTENSIONS, &mut num_extensions);
+
+		(0..num_extensions as u32)
+			.map(|index| std::ffi::CStr::from_ptr(gl::GetStringi(gl::EXTENSIONS, index) as *const _))
+			.map(|name| name.to_string_lossy().into_owned())
+			.collect()
+	}
+}
+
+
+
+// https://registry.khronos.org/KTX/specs/2.0/ktxspec.v2.html
+const KTX2_IDENTIFIER: [u8; 12] = [0xAB, 0x4B, 0x54, 0x58, 0x20, 0x32, 0x30, 0xBB, 0x0D, 0x0A, 0x1A, 0x0A];
+const KTX2_LEVEL_INDEX_OFFSET: usize = 80;
+
+fn parse_ktx2(data: &[u8]) -> anyhow::Result<CompressedImage> {
+	anyhow::ensure!(data.get(..12) == Some(&KTX2_IDENTIFIER[..]), "Missing KTX2 identifier");
+
+	let vk_format = read_u32(data, 12)?;
+	let width = read_u32(data, 20)?;
+	let height = read_u32(data, 24)?;
+	let depth = read_u32(data, 28)?;
+	let layer_count = read_u32(data, 32)?;
+	let face_count = read_u32(data, 36)?;
+	let level_count = read_u32(data, 40)?;
+	let supercompression_scheme = read_u32(data, 44)?;
+
+	anyhow::ensure!(supercompression_scheme == 0, "Supercompressed KTX2 files aren't supported (scheme {supercompression_scheme})");
+	anyhow::ensure!(depth == 0, "3D compressed textures aren't supported");
+	anyhow::ensure!(height > 0, "1D compressed textures aren't supported");
+	anyhow::ensure!(face_count == 1 || face_count == 6, "Invalid face count {face_count}");
+
+	let format = match vk_format {
+		131 => CompressedFormat::Bc1Rgb,
+		132 => CompressedFormat::Bc1RgbSrgb,
+		133 => CompressedFormat::Bc1Rgba,
+		134 => CompressedFormat::Bc1RgbaSrgb,
+		135 => CompressedFormat::Bc2,
+		136 => CompressedFormat::Bc2Srgb,
+		137 => CompressedFormat::Bc3,
+		138 => CompressedFormat::Bc3Srgb,
+		139 => CompressedFormat::Bc4Unorm,
+		140 => CompressedFormat::Bc4Snorm,
+		141 => CompressedFormat::Bc5Unorm,
+		142 => CompressedFormat::Bc5Snorm,
+		143 => CompressedFormat::Bc6hUfloat,
+		144 => CompressedFormat::Bc6hSfloat,
+		145 => CompressedFormat::Bc7,
+		146 => CompressedFormat::Bc7Srgb,
+		147 => CompressedFormat::Etc2Rgb,
+		148 => CompressedFormat::Etc2RgbSrgb,
+		149 => CompressedFormat::Etc2RgbA1,
+		150 => CompressedFormat::Etc2RgbA1Srgb,
+		151 => CompressedFormat::Etc2Rgba,
+		152 => CompressedFormat::Etc2RgbaSrgb,
+		_ => anyhow::bail!("Unsupported VkFormat {vk_format}"),
+	};
+
+	let size = image_size(width, height)?;
+	let layers = layer_count.max(1);
+
+	let images_per_level = layers.checked_mul(face_count)
+		.with_context(|| format!("{layers} layers of {face_count} faces is too many"))? as usize;
+
+	// A level count of zero asks the loader to generate mips, which isn't possible for compressed formats
+	let level_count = level_count.max(1);
+	check_level_count(level_count, size)?;
+
+	let mut levels = Vec::with_capacity(level_count as usize);
+
+	for level in 0..level_count as usize {
+		let entry_offset = KTX2_LEVEL_INDEX_OFFSET + level * 24;
+		let byte_offset = usize::try_from(read_u64(data, entry_offset)?)
+			.with_context(|| format!("Level {level} offset is out of range"))?;
+		let byte_length = usize::try_from(read_u64(data, entry_offset + 8)?)
+			.with_context(|| format!("Level {level} length is out of range"))?;
+
+		let expected_length = format.level_byte_size(mip_size(size, level)).checked_mul(images_per_level)
+			.with_context(|| format!("Level {level} is too large"))?;
+
+		anyhow::ensure!(byte_length == expected_length, "Level {level} is {byte_length} bytes, expected {expected_length}");
+
+		let level_end = byte_offset.checked_add(byte_length)
+			.with_context(|| format!("Level {level} data range overflows"))?;
+
+		let level_data = data.get(byte_offset..level_end)
+			.with_context(|| format!("Level {level} data is out of bounds"))?;
+
+		levels.push(level_data.to_vec());
+	}
+
+	Ok(CompressedImage {
+		format,
+		size,
+		layers,
+		faces: face_count,
+		levels,
+	})
+}
+
+
+
+// https://learn.microsoft.com/en-us/windows/win32/direct3ddds/dds-header
+const DDS_MAGIC: u32 = u32::from_le_bytes(*b"DDS ");
+const DDS_HEADER_END: usize = 128;
+const DDS_DX10_HEADER_END: usize = DDS_HEADER_END + 20;
+
+const DDSD_MIPMAPCOUNT: u32 = 0x20000;
+const DDPF_FOURCC: u32 = 0x4;
+const DDSCAPS2_CUBEMAP: u32 = 0x200;
+const DDSCAPS2_VOLUME: u32 = 0x200000;
+const DDS_RESOURCE_MISC_TEXTURECUBE: u32 = 0x4;
+const DDS_DIMENSION_TEXTURE2D: u32 = 3;
+
+fn parse_dds(data: &[u8]) -> anyhow::Result<CompressedImage> {
+	anyhow::ensure!(read_u32(data, 0)? == DDS_MAGIC, "Missing DDS magic");
+	anyhow::ensure!(read_u32(data, 4)? == 124, "Invalid DDS header size");
+
+	let flags = read_u32(data, 8)?;
+	let height = read_u32(data, 12)?;
+	let width = read_u32(data, 16)?;
+	let mip_map_count = read_u32(data, 28)?;
+	let pixel_format_flags = read_u32(data, 80)?;
+	let four_cc = read_u32(data, 84)?;
+	let caps2 = read_u32(data, 112)?;
+
+	anyhow::ensure!(pixel_format_flags & DDPF_FOURCC != 0, "Uncompressed DDS files aren't supported");
+	anyhow::ensure!(caps2 & DDSCAPS2_VOLUME == 0, "3D compressed textures aren't supported");
+
+	let (format, layers, faces, data_offset) = if four_cc == u32::from_le_bytes(*b"DX10") {
+		let dxgi_format = read_u32(data, 128)?;
+		let resource_dimension = read_u32(data, 132)?;
+		let misc_flag = read_u32(data, 136)?;
+		let array_size = read_u32(data, 140)?;
+
+		anyhow::ensure!(resource_dimension == DDS_DIMENSION_TEXTURE2D, "Only 2D DDS textures are supported");
+
+		let format = match dxgi_format {
+			71 => CompressedFormat::Bc1Rgba,
+			72 => CompressedFormat::Bc1RgbaSrgb,
+			74 => CompressedFormat::Bc2,
+			75 => CompressedFormat::Bc2Srgb,
+			77 => CompressedFormat::Bc3,
+			78 => CompressedFormat::Bc3Srgb,
+			80 => CompressedFormat::Bc4Unorm,
+			81 => CompressedFormat::Bc4Snorm,
+			83 => CompressedFormat::Bc5Unorm,
+			84 => CompressedFormat::Bc5Snorm,
+			95 => CompressedFormat::Bc6hUfloat,
+			96 => CompressedFormat::Bc6hSfloat,
+			98 => CompressedFormat::Bc7,
+			99 => CompressedFormat::Bc7Srgb,
+			_ => anyhow::bail!("Unsupported DXGI format {dxgi_format}"),
+		};
+
+		let faces = match misc_flag & DDS_RESOURCE_MISC_TEXTURECUBE != 0 {
+			true => 6,
+			false => 1,
+		};
+
+		(format, array_size.max(1), faces, DDS_DX10_HEADER_END)
+
+	} else {
+		let format = match &four_cc.to_le_bytes() {
+			b"DXT1" => CompressedFormat::Bc1Rgba,
+			b"DXT2" | b"DXT3" => CompressedFormat::Bc2,
+			b"DXT4" | b"DXT5" => CompressedFormat::Bc3,
+			b"ATI1" | b"BC4U" => CompressedFormat::Bc4Unorm,
+			b"BC4S" => CompressedFormat::Bc4Snorm,
+			b"ATI2" | b"BC5U" => CompressedFormat::Bc5Unorm,
+			b"BC5S" => CompressedFormat::Bc5Snorm,
+			other => anyhow::bail!("Unsupported FourCC '{}'", String::from_utf8_lossy(other)),
+		};
+
+		let faces = match caps2 & DDSCAPS2_CUBEMAP != 0 {
+			true => 6,
+			false => 1,
+		};
+
+		(format, 1, faces, DDS_HEADER_END)
+	};
+
+	let size = image_size(width, height)?;
+
+	let level_count = match flags & DDSD_MIPMAPCOUNT != 0 {
+		true => mip_map_count.max(1),
+		false => 1,
+	};
+
+	check_level_count(level_count, size)?;
+
+	// DDS stores each layer/face with its full mip chain, GL wants each level with all of its layers/faces.
+	let level_sizes: Vec<usize> = (0..level_count as usize)
+		.map(|level| format.level_byte_size(mip_size(size, level)))
+		.collect();
+
+	let num_images = layers.checked_mul(faces)
+		.with_context(|| format!("{layers} layers of {faces} faces is too many"))? as usize;
+
+	let surface_end = level_sizes.iter().try_fold(0usize, |total, &level_size| total.checked_add(level_size))
+		.and_then(|chain_size| chain_size.checked_mul(num_images))
+		.and_then(|surfaces_size| surfaces_size.checked_add(data_offset))
+		.context("DDS data size overflows")?;
+
+	let chain_size: usize = level_sizes.iter().sum();
+
+	let surface_data = data.get(data_offset..surface_end)
+		.context("DDS data is truncated")?;
+
+	let levels = level_sizes.iter().enumerate()
+		.map(|(level, &level_size)| {
+			let level_offset: usize = level_sizes[..level].iter().sum();
+
+			surface_data.chunks_exact(chain_size)
+				.flat_map(|chain| &chain[level_offset..level_offset + level_size])
+				.copied()
+				.collect()
+		})
+		.collect();
+
+	Ok(CompressedImage {
+		format,
+		size,
+		layers,
+		faces,
+		levels,
+	})
+}
+
+
+
+fn mip_size(size: Vec2i, level: usize) -> Vec2i {
+	let shift = |x: i32| x.checked_shr(level as u32).unwrap_or(0).max(1);
+	Vec2i::new(shift(size.x), shift(size.y))
+}
+
+fn image_size(width: u32, height: u32) -> anyhow::Result<Vec2i> {
+	let width = i32::try_from(width).with_context(|| format!("Width {width} is too large"))?;
+	let height = i32::try_from(height).with_context(|| format!("Height {height} is too large"))?;
+	Ok(Vec2i::new(width, height))
+}
+
+// Checked before anything is allocated or read, since the count comes straight from the file
+fn check_level_count(level_count: u32, size: Vec2i) -> anyhow::Result<()> {
+	let max_levels = mips::mip_count(size);
+	anyhow::ensure!(level_count <= max_levels, "{level_count} mip levels is more than a {size:?} image can have ({max_levels})");
+	Ok(())
+}
+
+fn read_u32(data: &[u8], offset: usize) -> anyhow::Result<u32> {
+	let bytes = data.get(offset..offset + 4).context("Unexpected end of file")?;
+	Ok(u32::from_le_bytes(bytes.try_into()?))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> anyhow::Result<u64> {
+	let bytes = data.get(offset..offset + 8).context("Unexpected end of file")?;
+	Ok(u64::from_le_bytes(bytes.try_into()?))
+}
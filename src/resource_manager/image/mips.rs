@@ -0,0 +1,193 @@
+use common::math::Vec2i;
+
+
+#[derive(Debug, Clone)]
+pub enum PixelData {
+	U8(Vec<u8>),
+	U16(Vec<u16>),
+}
+
+impl PixelData {
+	pub fn as_ptr(&self) -> *const std::ffi::c_void {
+		match self {
+			PixelData::U8(data) => data.as_ptr().cast(),
+			PixelData::U16(data) => data.as_ptr().cast(),
+		}
+	}
+
+	pub fn as_bytes(&self) -> &[u8] {
+		match self {
+			PixelData::U8(data) => data,
+			PixelData::U16(data) => unsafe {
+				std::slice::from_raw_parts(data.as_ptr().cast(), data.len() * 2)
+			},
+		}
+	}
+}
+
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum MipFilter {
+	Box,
+	Kaiser,
+}
+
+
+pub fn mip_count(size: Vec2i) -> u32 {
+	let max_dimension = size.x.max(size.y).max(1) as u32;
+	u32::BITS - max_dimension.leading_zeros()
+}
+
+
+/// Generates `levels` mip levels, including `base_level`.
+/// Filtering happens in linear space, so sRGB colour channels are decoded first and re-encoded afterwards.
+pub fn generate_mips(base_level: &PixelData, size: Vec2i, num_channels: usize, is_srgb: bool, filter: MipFilter, levels: u32)
+	-> Vec<(Vec2i, PixelData)>
+{
+	// Alpha is always linear
+	let num_srgb_channels = if is_srgb { num_channels.min(3) } else { 0 };
+	let is_srgb_channel = |index: usize| index % num_channels < num_srgb_channels;
+
+	let mut current: Vec<f32> = match base_level {
+		PixelData::U8(data) => data.iter().map(|&v| v as f32 / 255.0).collect(),
+		PixelData::U16(data) => data.iter().map(|&v| v as f32 / 65535.0).collect(),
+	};
+
+	for (index, value) in current.iter_mut().enumerate() {
+		if is_srgb_channel(index) {
+			*value = srgb_to_linear(*value);
+		}
+	}
+
+	let mut current_size = size;
+	let mut result = vec![(size, base_level.clone())];
+
+	for _ in 1..levels {
+		let next_size = Vec2i::new((current_size.x / 2).max(1), (current_size.y / 2).max(1));
+
+		// Separable - downsample horizontally then vertically
+		let horizontal = downsample_axis(&current, current_size, Vec2i::new(next_size.x, current_size.y), num_channels, filter, true);
+		current = downsample_axis(&horizontal, Vec2i::new(next_size.x, current_size.y), next_size, num_channels, filter, false);
+		current_size = next_size;
+
+		let encoded = current.iter().enumerate()
+			.map(|(index, &value)| match is_srgb_channel(index) {
+				true => linear_to_srgb(value),
+				false => value,
+			})
+			.map(|value| value.clamp(0.0, 1.0));
+
+		let data = match base_level {
+			PixelData::U8(_) => PixelData::U8(encoded.map(|v| (v * 255.0).round() as u8).collect()),
+			PixelData::U16(_) => PixelData::U16(encoded.map(|v| (v * 65535.0).round() as u16).collect()),
+		};
+
+		result.push((current_size, data));
+	}
+
+	result
+}
+
+
+fn downsample_axis(src: &[f32], src_size: Vec2i, dst_size: Vec2i, num_channels: usize, filter: MipFilter, horizontal: bool) -> Vec<f32> {
+	let (src_length, dst_length) = match horizontal {
+		true => (src_size.x, dst_size.x),
+		false => (src_size.y, dst_size.y),
+	};
+
+	// If this axis doesn't shrink (e.g., for 1xN images), there's nothing to filter
+	if dst_length == src_length {
+		return src.to_vec();
+	}
+
+	let taps = filter_taps(filter);
+	let mut dst = vec![0.0; (dst_size.x * dst_size.y) as usize * num_channels];
+
+	for y in 0..dst_size.y {
+		for x in 0..dst_size.x {
+			let dst_position = if horizontal { x } else { y };
+			let centre = (dst_position as f32 + 0.5) * 2.0;
+
+			for channel in 0..num_channels {
+				let mut sum = 0.0;
+				let mut weight_sum = 0.0;
+
+				for &(offset, weight) in taps.iter() {
+					let sample_position = (centre + offset).floor() as i32;
+					let sample_position = sample_position.clamp(0, src_length - 1);
+
+					let (src_x, src_y) = match horizontal {
+						true => (sample_position, y),
+						false => (x, sample_position),
+					};
+
+					let src_index = (src_x + src_y * src_size.x) as usize * num_channels + channel;
+					sum += src[src_index] * weight;
+					weight_sum += weight;
+				}
+
+				let dst_index = (x + y * dst_size.x) as usize * num_channels + channel;
+				dst[dst_index] = sum / weight_sum;
+			}
+		}
+	}
+
+	dst
+}
+
+
+// Sample offsets relative to the centre of the destination texel, in source texels, paired with weights.
+fn filter_taps(filter: MipFilter) -> Vec<(f32, f32)> {
+	match filter {
+		MipFilter::Box => vec![(-0.5, 1.0), (0.5, 1.0)],
+
+		MipFilter::Kaiser => {
+			const RADIUS: f32 = 3.0;
+			const ALPHA: f32 = 4.0;
+
+			[-2.5f32, -1.5, -0.5, 0.5, 1.5, 2.5].into_iter()
+				.map(|offset| {
+					// Sinc scaled for a factor of 2 downsample, windowed by a Kaiser window
+					let x = offset / 2.0;
+					let sinc = (std::f32::consts::PI * x).sin() / (std::f32::consts::PI * x);
+
+					let t = offset / RADIUS;
+					let window = bessel_i0(ALPHA * (1.0 - t*t).max(0.0).sqrt()) / bessel_i0(ALPHA);
+
+					(offset, sinc * window)
+				})
+				.collect()
+		}
+	}
+}
+
+// Zeroth order modified bessel function of the first kind
+fn bessel_i0(x: f32) -> f32 {
+	let mut sum = 1.0;
+	let mut term = 1.0;
+	let half_x = x / 2.0;
+
+	for k in 1..16 {
+		term *= half_x / k as f32;
+		sum += term * term;
+	}
+
+	sum
+}
+
+
+fn srgb_to_linear(value: f32) -> f32 {
+	if value <= 0.04045 {
+		value / 12.92
+	} else {
+		((value + 0.055) / 1.055).powf(2.4)
+	}
+}
+
+fn linear_to_srgb(value: f32) -> f32 {
+	if value <= 0.0031308 {
+		value * 12.92
+	} else {
+		1.055 * value.powf(1.0 / 2.4) - 0.055
+	}
+}
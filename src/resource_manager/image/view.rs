@@ -0,0 +1,111 @@
+use super::{ImageObject, ImageSize, TextureType};
+use crate::resource_manager::ImageHandle;
+use common::math::Vec2i;
+use std::ops::Range;
+
+
+/// A reinterpretation of part of another image's storage - see `ResourceManager::create_image_view`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ImageViewDef {
+	pub parent: ImageHandle,
+
+	/// Must be in the same view class as the parent's format - e.g., any 32 bit format for R11F_G11F_B10F,
+	/// or the linear equivalent of an sRGB format.
+	pub format: u32,
+
+	pub levels: Range<u32>,
+
+	/// Array layers or cubemap faces, with cube array faces addressed as `cube * 6 + face`. Ignored for 3D images.
+	pub layers: Range<u32>,
+}
+
+
+pub fn create_view(parent: &ImageObject, def: &ImageViewDef) -> anyhow::Result<ImageObject> {
+	let ImageViewDef{format, levels, layers, ..} = def;
+
+	let (is_immutable, allocated_levels) = unsafe {
+		let mut is_immutable = 0;
+		let mut allocated_levels = 0;
+		gl::GetTextureParameteriv(parent.name, gl::TEXTURE_IMMUTABLE_FORMAT, &mut is_immutable);
+		gl::GetTextureParameteriv(parent.name, gl::TEXTURE_IMMUTABLE_LEVELS, &mut allocated_levels);
+		(is_immutable != 0, allocated_levels as u32)
+	};
+
+	anyhow::ensure!(is_immutable, "Views can only be created of images with immutable storage");
+	anyhow::ensure!(!levels.is_empty() && levels.end <= allocated_levels,
+		"Mip range {levels:?} is outside of the {allocated_levels} levels of the parent image");
+
+	let parent_layers = match parent.texture_type {
+		TextureType::Cube => 6,
+		TextureType::CubeArray => parent.layers * 6,
+		TextureType::Tex3D => 1,
+		_ => parent.layers,
+	};
+
+	// 3D textures can't be viewed a slice at a time
+	let layers = match parent.texture_type {
+		TextureType::Tex3D => 0..1,
+		_ => layers.clone(),
+	};
+
+	anyhow::ensure!(!layers.is_empty() && layers.end <= parent_layers,
+		"Layer range {layers:?} is outside of the {parent_layers} layers of the parent image");
+
+	let num_layers = layers.end - layers.start;
+
+	let (texture_type, view_layers) = match (parent.texture_type, num_layers) {
+		(TextureType::Tex3D, _) => (TextureType::Tex3D, parent.layers),
+		(_, 1) => (TextureType::Tex2D, 1),
+		(TextureType::Cube | TextureType::CubeArray, 6) => (TextureType::Cube, 1),
+		(TextureType::CubeArray, _) if num_layers % 6 == 0 => (TextureType::CubeArray, num_layers / 6),
+		_ => (TextureType::Tex2DArray, num_layers),
+	};
+
+	let target = match parent.samples {
+		1 => texture_type.gl_target(),
+		_ => texture_type.gl_multisample_target()
+			.expect("Multisampled images are always 2D or 2D arrays"),
+	};
+
+	let view_class = |format| unsafe {
+		let mut class = 0;
+		gl::GetInternalformativ(target, format, gl::VIEW_COMPATIBILITY_CLASS, 1, &mut class);
+		class as u32
+	};
+
+	let parent_class = view_class(parent.format);
+	anyhow::ensure!(*format == parent.format || (parent_class != gl::NONE && parent_class == view_class(*format)),
+		"Can't view image with format {:#x} as {format:#x}", parent.format);
+
+	let resolved_size = Vec2i::new(
+		(parent.resolved_size.x >> levels.start).max(1),
+		(parent.resolved_size.y >> levels.start).max(1),
+	);
+
+	let label = format!("view {format:#x}, levels {levels:?}, layers {layers:?}");
+	let mut name = 0;
+
+	unsafe {
+		// Views need a name that has never been bound, which CreateTextures doesn't give us
+		gl::GenTextures(1, &mut name);
+		gl::TextureView(name, target, parent.name, *format,
+			levels.start, levels.end - levels.start,
+			layers.start, num_layers);
+
+		gl::ObjectLabel(gl::TEXTURE, name, label.len() as i32, label.as_ptr() as *const _);
+	}
+
+	Ok(ImageObject {
+		name,
+
+		// Views are resized with their parents, so this is only informational
+		size: ImageSize::Fixed(resolved_size),
+		resolved_size,
+		format: *format,
+		levels: levels.end - levels.start,
+		requested_levels: levels.end - levels.start,
+		samples: parent.samples,
+		texture_type,
+		layers: view_layers,
+	})
+}
@@ -0,0 +1,332 @@
+use crate::resource_manager::{ResourceManager, ResourcePath, ResourcePathRef, ShaderHandle, ProgramHandle, ImageHandle};
+use crate::resource_manager::{ShaderDef, ShaderType, ImageDef, ImageObject, ImageSize, ImageLoadOptions};
+use crate::resource_manager::{SamplerDef, AddressingMode, FilterMode, CompareFunction};
+use common::math::Vec2i;
+use anyhow::Context;
+use serde::Deserialize;
+use std::collections::{HashMap, BTreeMap};
+use std::time::SystemTime;
+
+
+/// Named resources declared in a RON manifest, e.g.
+///
+/// ```ron
+/// (
+/// 	shaders: {
+/// 		"test_vert": (path: "shaders/test.vert.glsl", stage: Vertex),
+/// 	},
+/// 	programs: {
+/// 		"sprite": "shaders/sprite.glsl",
+/// 	},
+/// 	images: {
+/// 		"coolcat": (path: "images/coolcat.png", options: (mips: Gpu)),
+/// 	},
+/// 	render_targets: {
+/// 		"hdr": (format: "RGBA16F", size: Scaled(1, 2)),
+/// 	},
+/// 	samplers: {
+/// 		"pixel": (filter: Nearest, addressing: ClampToEdge),
+/// 	},
+/// )
+/// ```
+#[derive(Debug)]
+pub struct Manifest {
+	pub path: ResourcePath,
+	pub(super) last_modified: SystemTime,
+
+	shaders: HashMap<String, ShaderHandle>,
+	programs: HashMap<String, ProgramHandle>,
+	images: HashMap<String, ImageHandle>,
+	samplers: HashMap<String, SamplerDef>,
+
+	// Render targets aren't shared, so keep their defs around to avoid recreating them on every reload
+	render_targets: HashMap<String, (ImageDef, ImageHandle)>,
+}
+
+impl Manifest {
+	pub fn shader(&self, name: &str) -> Option<ShaderHandle> {
+		self.shaders.get(name).copied()
+	}
+
+	pub fn program(&self, name: &str) -> Option<ProgramHandle> {
+		self.programs.get(name).copied()
+	}
+
+	/// Includes render targets.
+	pub fn image(&self, name: &str) -> Option<ImageHandle> {
+		self.images.get(name).copied()
+			.or_else(|| self.render_targets.get(name).map(|&(_, handle)| handle))
+	}
+
+	pub fn sampler(&self, name: &str) -> Option<SamplerDef> {
+		self.samplers.get(name).copied()
+	}
+
+	pub(super) fn render_target_handles(&self) -> impl Iterator<Item=ImageHandle> + '_ {
+		self.render_targets.values().map(|&(_, handle)| handle)
+	}
+}
+
+
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct ManifestFile {
+	#[serde(default)]
+	shaders: BTreeMap<String, ShaderEntry>,
+	#[serde(default)]
+	programs: BTreeMap<String, ResourcePath>,
+	#[serde(default)]
+	images: BTreeMap<String, ImageEntry>,
+	#[serde(default)]
+	render_targets: BTreeMap<String, RenderTargetEntry>,
+	#[serde(default)]
+	samplers: BTreeMap<String, SamplerEntry>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct ShaderEntry {
+	path: ResourcePath,
+	stage: ShaderType,
+
+	#[serde(default)]
+	defines: Vec<(String, String)>,
+	#[serde(default)]
+	features: Vec<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct ImageEntry {
+	path: ResourcePath,
+
+	#[serde(default)]
+	options: ImageLoadOptions,
+
+	/// Load with `ResourceManager::load_image_async`.
+	#[serde(default)]
+	streamed: bool,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(deny_unknown_fields)]
+struct RenderTargetEntry {
+	/// The GL internal format without the GL_ prefix, e.g. "RGBA16F".
+	format: String,
+
+	#[serde(default)]
+	size: SizeEntry,
+
+	#[serde(default = "default_one")]
+	levels: u32,
+	#[serde(default = "default_one")]
+	samples: u32,
+}
+
+#[derive(Deserialize, Debug, Default)]
+enum SizeEntry {
+	#[default]
+	Backbuffer,
+	Scaled(u32, u32),
+	Fixed(i32, i32),
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(default, deny_unknown_fields)]
+struct SamplerEntry {
+	addressing: AddressingMode,
+	filter: FilterMode,
+	mip_filter: Option<FilterMode>,
+	anisotropy: f32,
+	compare: Option<CompareFunction>,
+}
+
+impl Default for SamplerEntry {
+	fn default() -> SamplerEntry {
+		SamplerEntry {
+			addressing: AddressingMode::Repeat,
+			filter: FilterMode::Linear,
+			mip_filter: None,
+			anisotropy: 1.0,
+			compare: None,
+		}
+	}
+}
+
+fn default_one() -> u32 { 1 }
+
+
+
+/// Loads everything declared in the manifest at `path`. Render targets that were in `previous` keep their handles -
+/// if their def has changed their storage is recreated in place, so handles held elsewhere stay valid across reloads.
+/// Nothing is created or changed if any entry fails to load, except for shaders and images which are cached anyway.
+pub(super) fn load(resource_manager: &mut ResourceManager, path: &ResourcePathRef, previous: Option<&Manifest>) -> anyhow::Result<Manifest> {
+	let last_modified = resource_manager.latest_modification_time(&[path.to_owned()]);
+	let content = resource_manager.load_text(path)
+		.with_context(|| format!("Failed to read manifest '{}'", path.display()))?;
+
+	let file: ManifestFile = ron::from_str(&content)
+		.with_context(|| format!("Failed to parse manifest '{}'", path.display()))?;
+
+	let mut manifest = Manifest {
+		path: path.to_owned(),
+		last_modified,
+
+		shaders: HashMap::new(),
+		programs: HashMap::new(),
+		images: HashMap::new(),
+		samplers: HashMap::new(),
+		render_targets: HashMap::new(),
+	};
+
+	for (name, entry) in file.shaders {
+		let mut def = ShaderDef::new(entry.path, entry.stage);
+		def.defines = entry.defines;
+		def.features.extend(entry.features);
+
+		let handle = resource_manager.load_shader(&def)
+			.with_context(|| format!("Failed to load shader '{name}'"))?;
+
+		manifest.shaders.insert(name, handle);
+	}
+
+	for (name, path) in file.programs {
+		let handle = resource_manager.load_program(path)
+			.with_context(|| format!("Failed to load program '{name}'"))?;
+
+		manifest.programs.insert(name, handle);
+	}
+
+	for (name, entry) in file.images {
+		let def = ImageDef::with_options(entry.path, entry.options);
+
+		let handle = match entry.streamed {
+			true => resource_manager.load_image_async(&def),
+			false => resource_manager.load_image(&def),
+		};
+
+		let handle = handle.with_context(|| format!("Failed to load image '{name}'"))?;
+		manifest.images.insert(name, handle);
+	}
+
+	for (name, entry) in file.samplers {
+		let mut def = SamplerDef::default()
+			.addressing(entry.addressing)
+			.filter(entry.filter, entry.filter)
+			.anisotropy(entry.anisotropy);
+
+		def.mip_filter = entry.mip_filter;
+		def.compare_function = entry.compare;
+
+		manifest.samplers.insert(name, def);
+	}
+
+	// Validate everything up front so that a bad entry doesn't leave half the render targets created
+	let mut render_target_defs = Vec::new();
+
+	for (name, entry) in file.render_targets {
+		let Some(format) = parse_format(&entry.format) else {
+			anyhow::bail!("Render target '{name}' has unknown format '{}'", entry.format);
+		};
+
+		let size = match entry.size {
+			SizeEntry::Backbuffer => ImageSize::Backbuffer,
+			SizeEntry::Scaled(_, 0) => anyhow::bail!("Render target '{name}' has a scaled size with a zero denominator"),
+			SizeEntry::Scaled(numerator, denominator) => ImageSize::BackbufferScaled(numerator, denominator),
+			SizeEntry::Fixed(width, height) => ImageSize::Fixed(Vec2i::new(width, height)),
+		};
+
+		let def = ImageDef::render_target(format)
+			.size(size)
+			.levels(entry.levels)
+			.samples(entry.samples);
+
+		render_target_defs.push((name, def));
+	}
+
+	// Changed render targets get new storage, but it's only swapped in once everything has loaded successfully
+	let mut replaced_objects = Vec::new();
+
+	for (name, def) in render_target_defs {
+		let existing = previous.and_then(|previous| previous.render_targets.get(&name));
+
+		let result = match existing {
+			Some((previous_def, handle)) if *previous_def == def => Ok(*handle),
+
+			Some(&(_, handle)) => super::image::load(resource_manager, &def).map(|object| {
+				replaced_objects.push((handle, object));
+				handle
+			}),
+
+			None => resource_manager.load_image(&def),
+		};
+
+		let handle = match result {
+			Ok(handle) => handle,
+			Err(error) => {
+				discard_created_render_targets(resource_manager, &manifest, previous, replaced_objects);
+				return Err(error.context(format!("Failed to create render target '{name}'")))
+			}
+		};
+
+		manifest.render_targets.insert(name, (def, handle));
+	}
+
+	resource_manager.replace_image_objects(replaced_objects);
+
+	Ok(manifest)
+}
+
+fn discard_created_render_targets(resource_manager: &mut ResourceManager, manifest: &Manifest, previous: Option<&Manifest>,
+	replaced_objects: Vec<(ImageHandle, ImageObject)>)
+{
+	// Only destroy what we created - anything reused still belongs to the previous manifest
+	let previous_handles: Vec<_> = previous.into_iter().flat_map(Manifest::render_target_handles).collect();
+	let created_handles: Vec<_> = manifest.render_target_handles()
+		.filter(|handle| !previous_handles.contains(handle))
+		.collect();
+
+	for handle in created_handles {
+		resource_manager.destroy_image(handle);
+	}
+
+	// Replacement storage was never swapped in, so nothing can be using it yet
+	for (_, object) in replaced_objects {
+		unsafe {
+			gl::DeleteTextures(1, &object.name);
+		}
+	}
+}
+
+fn parse_format(name: &str) -> Option<u32> {
+	let format = match name {
+		"R8" => gl::R8,
+		"RG8" => gl::RG8,
+		"RGBA8" => gl::RGBA8,
+		"SRGB8_ALPHA8" => gl::SRGB8_ALPHA8,
+		"RGB10_A2" => gl::RGB10_A2,
+
+		"R16F" => gl::R16F,
+		"RG16F" => gl::RG16F,
+		"RGBA16F" => gl::RGBA16F,
+		"R32F" => gl::R32F,
+		"RG32F" => gl::RG32F,
+		"RGBA32F" => gl::RGBA32F,
+		"R11F_G11F_B10F" => gl::R11F_G11F_B10F,
+
+		"R32UI" => gl::R32UI,
+		"RG32UI" => gl::RG32UI,
+		"RGBA32UI" => gl::RGBA32UI,
+
+		"DEPTH_COMPONENT16" => gl::DEPTH_COMPONENT16,
+		"DEPTH_COMPONENT24" => gl::DEPTH_COMPONENT24,
+		"DEPTH_COMPONENT32F" => gl::DEPTH_COMPONENT32F,
+		"DEPTH24_STENCIL8" => gl::DEPTH24_STENCIL8,
+		"DEPTH32F_STENCIL8" => gl::DEPTH32F_STENCIL8,
+
+		_ => return None,
+	};
+
+	Some(format)
+}
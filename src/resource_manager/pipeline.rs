@@ -1,4 +1,5 @@
-use super::{ResourceManager, ShaderHandle, shader::BlockDescription};
+use super::{ResourceManager, ShaderHandle, ProgramHandle, GfxError};
+use super::shader::{BlockDescription, ImageBindingDescription, ImageBindingKind};
 use std::collections::HashMap;
 
 
@@ -9,11 +10,27 @@ pub struct PipelineDef {
 	pub compute: Option<ShaderHandle>,
 }
 
+impl PipelineDef {
+	pub fn shaders(&self) -> impl Iterator<Item=ShaderHandle> {
+		[self.vertex, self.fragment, self.compute].into_iter().flatten()
+	}
+}
+
+impl From<ProgramHandle> for PipelineDef {
+	fn from(program: ProgramHandle) -> PipelineDef {
+		PipelineDef {
+			vertex: program.vertex,
+			fragment: program.fragment,
+			compute: program.compute,
+		}
+	}
+}
+
 #[derive(Debug)]
 pub struct PipelineObject {
 	pub name: u32,
 	pub composite_blocks: HashMap<String, BlockDescription>,
-	pub composite_image_bindings: HashMap<String, u32>,
+	pub composite_image_bindings: HashMap<String, ImageBindingDescription>,
 }
 
 impl PipelineObject {
@@ -25,13 +42,17 @@ impl PipelineObject {
 		self.composite_blocks.values().find(move |desc| desc.binding_location == loc)
 	}
 
-	pub fn image_binding_by_name(&self, name: &str) -> Option<u32> {
-		self.composite_image_bindings.get(name).cloned()
+	pub fn image_binding_by_name(&self, name: &str) -> Option<&ImageBindingDescription> {
+		self.composite_image_bindings.get(name)
+	}
+
+	pub fn image_binding_by_unit(&self, kind: ImageBindingKind, unit: u32) -> Option<&ImageBindingDescription> {
+		self.composite_image_bindings.values().find(move |desc| desc.kind == kind && desc.unit == unit)
 	}
 }
 
 
-pub fn create_pipeline(resource_manager: &ResourceManager, def: &PipelineDef) -> anyhow::Result<PipelineObject> {
+pub fn create_pipeline(resource_manager: &ResourceManager, def: &PipelineDef) -> Result<PipelineObject, GfxError> {
 	let mut pipeline_name = 0;
 	let mut composite_blocks = HashMap::new();
 	let mut composite_image_bindings = HashMap::new();
@@ -39,12 +60,24 @@ pub fn create_pipeline(resource_manager: &ResourceManager, def: &PipelineDef) ->
 	unsafe {
 		gl::CreateProgramPipelines(1, &mut pipeline_name);
 		if pipeline_name == 0 {
-			anyhow::bail!("Failed to create pipeline pipeline");
+			return Err(GfxError::PipelineCreationFailed);
 		}
 
-		bind_shader_to_pipeline(resource_manager, pipeline_name, def.vertex, gl::VERTEX_SHADER_BIT, &mut composite_blocks, &mut composite_image_bindings);
-		bind_shader_to_pipeline(resource_manager, pipeline_name, def.fragment, gl::FRAGMENT_SHADER_BIT, &mut composite_blocks, &mut composite_image_bindings);
-		bind_shader_to_pipeline(resource_manager, pipeline_name, def.compute, gl::COMPUTE_SHADER_BIT, &mut composite_blocks, &mut composite_image_bindings);
+		let stages = [
+			(def.vertex, gl::VERTEX_SHADER_BIT),
+			(def.fragment, gl::FRAGMENT_SHADER_BIT),
+			(def.compute, gl::COMPUTE_SHADER_BIT),
+		];
+
+		for (shader_handle, type_bits) in stages {
+			let result = bind_shader_to_pipeline(resource_manager, pipeline_name, shader_handle, type_bits,
+				&mut composite_blocks, &mut composite_image_bindings);
+
+			if let Err(error) = result {
+				gl::DeleteProgramPipelines(1, &pipeline_name);
+				return Err(error)
+			}
+		}
 
 		// TODO(pat.m): gl::ObjectLabel
 
@@ -60,13 +93,16 @@ pub fn create_pipeline(resource_manager: &ResourceManager, def: &PipelineDef) ->
 
 
 fn bind_shader_to_pipeline(resource_manager: &ResourceManager, pipeline_name: u32, shader_handle: Option<ShaderHandle>,
-	type_bits: u32, composite_blocks: &mut HashMap<String, BlockDescription>, composite_image_bindings: &mut HashMap<String, u32>)
+	type_bits: u32, composite_blocks: &mut HashMap<String, BlockDescription>, composite_image_bindings: &mut HashMap<String, ImageBindingDescription>)
+	-> Result<(), GfxError>
 {
 	let Some(shader_handle) = shader_handle else {
-		return
+		return Ok(())
 	};
 
-	let shader_object = resource_manager.resolve_shader(shader_handle).unwrap();
+	let shader_object = resource_manager.resolve_shader(shader_handle)
+		.ok_or(GfxError::MissingShader(shader_handle))?;
+
 	unsafe {
 		gl::UseProgramStages(pipeline_name, type_bits, shader_object.name);
 	}
@@ -77,17 +113,19 @@ fn bind_shader_to_pipeline(resource_manager: &ResourceManager, pipeline_name: u3
 		if let Some(prev_block) = prev_block
 			&& prev_block != *block
 		{
-			panic!("Pipeline contains multiple incompatible interface blocks with same name '{block_name}'");
+			return Err(GfxError::IncompatibleInterfaceBlocks { name: block_name.clone() })
 		}
 	}
 
-	for (uniform_name, index) in shader_object.image_bindings.iter() {
-		let prev_index = composite_image_bindings.insert(uniform_name.clone(), *index);
+	for (uniform_name, binding) in shader_object.image_bindings.iter() {
+		let prev_binding = composite_image_bindings.insert(uniform_name.clone(), *binding);
 
-		if let Some(prev_index) = prev_index
-			&& prev_index != *index
+		if let Some(prev_binding) = prev_binding
+			&& prev_binding != *binding
 		{
-			panic!("Pipeline contains multiple image binding of same name '{uniform_name}' with different binding indices");
+			return Err(GfxError::IncompatibleImageBindings { name: uniform_name.clone() })
 		}
 	}
+
+	Ok(())
 }
\ No newline at end of file
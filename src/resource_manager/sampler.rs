@@ -1,43 +1,198 @@
 
 
-#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, serde::Deserialize)]
 #[repr(u32)]
 pub enum AddressingMode {
 	/// This is the default
 	Repeat = gl::REPEAT,
+	MirroredRepeat = gl::MIRRORED_REPEAT,
 	ClampToEdge = gl::CLAMP_TO_EDGE,
+	MirrorClampToEdge = gl::MIRROR_CLAMP_TO_EDGE,
+
+	/// Samples outside of the image return `SamplerDef::border_color`.
+	ClampToBorder = gl::CLAMP_TO_BORDER,
 }
 
-#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, serde::Deserialize)]
 #[repr(u32)]
 pub enum FilterMode {
 	Nearest = gl::NEAREST,
 	Linear = gl::LINEAR,
 }
 
-#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, serde::Deserialize)]
+#[repr(u32)]
+pub enum CompareFunction {
+	Never = gl::NEVER,
+	Less = gl::LESS,
+	Equal = gl::EQUAL,
+	LessEqual = gl::LEQUAL,
+	Greater = gl::GREATER,
+	NotEqual = gl::NOTEQUAL,
+	GreaterEqual = gl::GEQUAL,
+	Always = gl::ALWAYS,
+}
+
+
+/// Float parameters are compared and hashed by their bit patterns so that SamplerDef can key the sampler cache.
+#[derive(Debug, Copy, Clone)]
 pub struct SamplerDef {
-	pub addressing_mode: AddressingMode,
+	/// Per axis - S, T, R.
+	pub addressing_mode: [AddressingMode; 3],
 	pub minify_filter: FilterMode,
 	pub magnify_filter: FilterMode,
+
+	/// Filtering between mip levels. None samples only the base level.
+	pub mip_filter: Option<FilterMode>,
+
+	/// 1.0 disables anisotropic filtering. Clamped to what the implementation supports.
+	pub max_anisotropy: f32,
+
+	/// Only used with `AddressingMode::ClampToBorder`.
+	pub border_color: [f32; 4],
+
+	pub lod_bias: f32,
+	pub min_lod: f32,
+	pub max_lod: f32,
+
+	/// Enables depth comparison for sampler*Shadow uniforms.
+	pub compare_function: Option<CompareFunction>,
+}
+
+impl Default for SamplerDef {
+	fn default() -> SamplerDef {
+		// Matches GL defaults, except for mipmapping
+		SamplerDef {
+			addressing_mode: [AddressingMode::Repeat; 3],
+			minify_filter: FilterMode::Linear,
+			magnify_filter: FilterMode::Linear,
+			mip_filter: None,
+			max_anisotropy: 1.0,
+			border_color: [0.0; 4],
+			lod_bias: 0.0,
+			min_lod: -1000.0,
+			max_lod: 1000.0,
+			compare_function: None,
+		}
+	}
 }
 
 impl SamplerDef {
 	pub fn linear_clamped() -> SamplerDef {
 		SamplerDef {
-			addressing_mode: AddressingMode::ClampToEdge,
+			addressing_mode: [AddressingMode::ClampToEdge; 3],
 			minify_filter: FilterMode::Linear,
 			magnify_filter: FilterMode::Linear,
+			.. SamplerDef::default()
 		}
 	}
 
 	pub fn nearest_clamped() -> SamplerDef {
 		SamplerDef {
-			addressing_mode: AddressingMode::ClampToEdge,
+			addressing_mode: [AddressingMode::ClampToEdge; 3],
 			minify_filter: FilterMode::Nearest,
 			magnify_filter: FilterMode::Nearest,
+			.. SamplerDef::default()
 		}
 	}
+
+	/// Trilinear, anisotropic and repeating - for regular mipmapped textures.
+	pub fn trilinear_repeat() -> SamplerDef {
+		SamplerDef::default()
+			.mip_filter(FilterMode::Linear)
+			.anisotropy(16.0)
+	}
+
+	/// Hardware PCF for shadow maps. Anything outside the map is lit.
+	pub fn shadow() -> SamplerDef {
+		SamplerDef::default()
+			.addressing(AddressingMode::ClampToBorder)
+			.border_color([1.0; 4])
+			.compare(CompareFunction::LessEqual)
+	}
+
+	pub fn addressing(mut self, mode: AddressingMode) -> SamplerDef {
+		self.addressing_mode = [mode; 3];
+		self
+	}
+
+	pub fn addressing_per_axis(mut self, s: AddressingMode, t: AddressingMode, r: AddressingMode) -> SamplerDef {
+		self.addressing_mode = [s, t, r];
+		self
+	}
+
+	pub fn filter(mut self, minify_filter: FilterMode, magnify_filter: FilterMode) -> SamplerDef {
+		self.minify_filter = minify_filter;
+		self.magnify_filter = magnify_filter;
+		self
+	}
+
+	pub fn mip_filter(mut self, mip_filter: FilterMode) -> SamplerDef {
+		self.mip_filter = Some(mip_filter);
+		self
+	}
+
+	pub fn anisotropy(mut self, max_anisotropy: f32) -> SamplerDef {
+		self.max_anisotropy = max_anisotropy;
+		self
+	}
+
+	pub fn border_color(mut self, border_color: [f32; 4]) -> SamplerDef {
+		self.border_color = border_color;
+		self
+	}
+
+	pub fn lod_bias(mut self, lod_bias: f32) -> SamplerDef {
+		self.lod_bias = lod_bias;
+		self
+	}
+
+	pub fn lod_range(mut self, min_lod: f32, max_lod: f32) -> SamplerDef {
+		self.min_lod = min_lod;
+		self.max_lod = max_lod;
+		self
+	}
+
+	pub fn compare(mut self, compare_function: CompareFunction) -> SamplerDef {
+		self.compare_function = Some(compare_function);
+		self
+	}
+
+	fn gl_minify_filter(&self) -> u32 {
+		match (self.minify_filter, self.mip_filter) {
+			(filter, None) => filter as u32,
+			(FilterMode::Nearest, Some(FilterMode::Nearest)) => gl::NEAREST_MIPMAP_NEAREST,
+			(FilterMode::Linear, Some(FilterMode::Nearest)) => gl::LINEAR_MIPMAP_NEAREST,
+			(FilterMode::Nearest, Some(FilterMode::Linear)) => gl::NEAREST_MIPMAP_LINEAR,
+			(FilterMode::Linear, Some(FilterMode::Linear)) => gl::LINEAR_MIPMAP_LINEAR,
+		}
+	}
+
+	fn bit_key(&self) -> impl std::hash::Hash + Eq {
+		(
+			self.addressing_mode,
+			self.minify_filter,
+			self.magnify_filter,
+			self.mip_filter,
+			self.compare_function,
+			self.border_color.map(f32::to_bits),
+			[self.max_anisotropy, self.lod_bias, self.min_lod, self.max_lod].map(f32::to_bits),
+		)
+	}
+}
+
+impl PartialEq for SamplerDef {
+	fn eq(&self, other: &SamplerDef) -> bool {
+		self.bit_key() == other.bit_key()
+	}
+}
+
+impl Eq for SamplerDef {}
+
+impl std::hash::Hash for SamplerDef {
+	fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+		self.bit_key().hash(state);
+	}
 }
 
 
@@ -50,17 +205,33 @@ pub struct SamplerObject {
 pub fn create_sampler(def: &SamplerDef) -> SamplerObject {
 	let mut sampler_name = 0;
 
-	let debug_label = format!("min: {:?}, mag: {:?}, address: {:?}",
-		def.minify_filter, def.magnify_filter, def.addressing_mode);
+	let debug_label = format!("min: {:?}, mag: {:?}, mip: {:?}, address: {:?}, compare: {:?}",
+		def.minify_filter, def.magnify_filter, def.mip_filter, def.addressing_mode, def.compare_function);
+
+	let [wrap_s, wrap_t, wrap_r] = def.addressing_mode;
 
 	unsafe {
 		gl::CreateSamplers(1, &mut sampler_name);
-		gl::SamplerParameteri(sampler_name, gl::TEXTURE_MIN_FILTER, def.minify_filter as i32);
+		gl::SamplerParameteri(sampler_name, gl::TEXTURE_MIN_FILTER, def.gl_minify_filter() as i32);
 		gl::SamplerParameteri(sampler_name, gl::TEXTURE_MAG_FILTER, def.magnify_filter as i32);
 
-		gl::SamplerParameteri(sampler_name, gl::TEXTURE_WRAP_S, def.addressing_mode as i32);
-		gl::SamplerParameteri(sampler_name, gl::TEXTURE_WRAP_T, def.addressing_mode as i32);
-		gl::SamplerParameteri(sampler_name, gl::TEXTURE_WRAP_R, def.addressing_mode as i32);
+		gl::SamplerParameteri(sampler_name, gl::TEXTURE_WRAP_S, wrap_s as i32);
+		gl::SamplerParameteri(sampler_name, gl::TEXTURE_WRAP_T, wrap_t as i32);
+		gl::SamplerParameteri(sampler_name, gl::TEXTURE_WRAP_R, wrap_r as i32);
+		gl::SamplerParameterfv(sampler_name, gl::TEXTURE_BORDER_COLOR, def.border_color.as_ptr());
+
+		let mut supported_anisotropy = 1.0;
+		gl::GetFloatv(gl::MAX_TEXTURE_MAX_ANISOTROPY, &mut supported_anisotropy);
+		gl::SamplerParameterf(sampler_name, gl::TEXTURE_MAX_ANISOTROPY, def.max_anisotropy.clamp(1.0, supported_anisotropy));
+
+		gl::SamplerParameterf(sampler_name, gl::TEXTURE_LOD_BIAS, def.lod_bias);
+		gl::SamplerParameterf(sampler_name, gl::TEXTURE_MIN_LOD, def.min_lod);
+		gl::SamplerParameterf(sampler_name, gl::TEXTURE_MAX_LOD, def.max_lod);
+
+		if let Some(compare_function) = def.compare_function {
+			gl::SamplerParameteri(sampler_name, gl::TEXTURE_COMPARE_MODE, gl::COMPARE_REF_TO_TEXTURE as i32);
+			gl::SamplerParameteri(sampler_name, gl::TEXTURE_COMPARE_FUNC, compare_function as i32);
+		}
 
 		gl::ObjectLabel(gl::SAMPLER, sampler_name, debug_label.len() as i32, debug_label.as_ptr() as *const _);
 	}
@@ -68,4 +239,4 @@ pub fn create_sampler(def: &SamplerDef) -> SamplerObject {
 	SamplerObject {
 		name: sampler_name
 	}
-}
\ No newline at end of file
+}
@@ -1,8 +1,20 @@
 use super::{ResourceManager, ResourcePath};
 use common::Vec3i;
-use std::collections::HashMap;
+use std::collections::{HashMap, BTreeSet};
 
-#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub mod preprocess;
+pub mod diagnostics;
+pub mod binary_cache;
+pub mod spirv;
+
+pub use self::diagnostics::{ShaderCompileError, ShaderDiagnostic};
+pub use self::binary_cache::ProgramBinaryCache;
+
+use self::preprocess::PreprocessedShader;
+
+pub const DEFAULT_GLSL_VERSION: u32 = 450;
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash, serde::Deserialize)]
 #[repr(u32)]
 pub enum ShaderType {
 	Vertex = gl::VERTEX_SHADER,
@@ -10,32 +22,88 @@ pub enum ShaderType {
 	Compute = gl::COMPUTE_SHADER,
 }
 
+#[derive(Copy, Clone, Debug, Eq, PartialEq, Hash)]
+pub enum GlslProfile {
+	Core,
+	Compatibility,
+}
+
 #[derive(Hash, Clone, Debug, Eq, PartialEq)]
 pub struct ShaderDef {
 	pub path: ResourcePath,
 	pub shader_type: ShaderType,
+
+	pub defines: Vec<(String, String)>,
+
+	/// Feature keys selecting a variant of this shader - each is defined as 1 when compiling.
+	pub features: BTreeSet<String>,
+
+	pub glsl_version: u32,
+	pub glsl_profile: GlslProfile,
+
+	/// Only used by SPIR-V modules.
+	pub entry_point: String,
+	/// Only used by SPIR-V modules - pairs of constant id and value bits.
+	pub specialization_constants: Vec<(u32, u32)>,
 }
 
 impl ShaderDef {
-	pub fn vertex(path: impl Into<ResourcePath>) -> ShaderDef {
+	pub fn new(path: impl Into<ResourcePath>, shader_type: ShaderType) -> ShaderDef {
 		ShaderDef {
 			path: path.into(),
-			shader_type: ShaderType::Vertex,
+			shader_type,
+
+			defines: Vec::new(),
+			features: BTreeSet::new(),
+			glsl_version: DEFAULT_GLSL_VERSION,
+			glsl_profile: GlslProfile::Core,
+
+			entry_point: String::from("main"),
+			specialization_constants: Vec::new(),
 		}
 	}
 
+	pub fn vertex(path: impl Into<ResourcePath>) -> ShaderDef {
+		ShaderDef::new(path, ShaderType::Vertex)
+	}
+
 	pub fn fragment(path: impl Into<ResourcePath>) -> ShaderDef {
-		ShaderDef {
-			path: path.into(),
-			shader_type: ShaderType::Fragment,
-		}
+		ShaderDef::new(path, ShaderType::Fragment)
 	}
 
 	pub fn compute(path: impl Into<ResourcePath>) -> ShaderDef {
-		ShaderDef {
-			path: path.into(),
-			shader_type: ShaderType::Compute,
-		}
+		ShaderDef::new(path, ShaderType::Compute)
+	}
+
+	pub fn define(mut self, name: impl Into<String>, value: impl Into<String>) -> ShaderDef {
+		self.defines.push((name.into(), value.into()));
+		self
+	}
+
+	pub fn feature(mut self, key: impl Into<String>) -> ShaderDef {
+		self.features.insert(key.into());
+		self
+	}
+
+	pub fn entry_point(mut self, entry_point: impl Into<String>) -> ShaderDef {
+		self.entry_point = entry_point.into();
+		self
+	}
+
+	pub fn specialize(mut self, constant_id: u32, value: u32) -> ShaderDef {
+		self.specialization_constants.retain(|&(id, _)| id != constant_id);
+		self.specialization_constants.push((constant_id, value));
+		self
+	}
+
+	pub fn is_spirv(&self) -> bool {
+		self.path.extension().map_or(false, |extension| extension == "spv")
+	}
+
+	pub fn version(mut self, glsl_version: u32, glsl_profile: GlslProfile) -> ShaderDef {
+		self.glsl_version = glsl_version;
+		self.glsl_profile = glsl_profile;
+		self
 	}
 }
 
@@ -55,28 +123,136 @@ pub struct BlockDescription {
 }
 
 
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ImageBindingKind {
+	/// Bound with BindTextureUnit - sampler* uniforms
+	Sampler,
+	/// Bound with BindImageTexture - image* uniforms
+	Image,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ImageDimensionality {
+	Dim1D,
+	Dim2D,
+	Dim3D,
+	Cube,
+	Rect,
+	Buffer,
+	Dim1DArray,
+	Dim2DArray,
+	CubeArray,
+	Dim2DMultisample,
+	Dim2DMultisampleArray,
+}
+
+impl ImageDimensionality {
+	/// Whether the uniform addresses multiple layers, faces or slices - and so needs a layered image binding.
+	pub fn is_layered(self) -> bool {
+		use ImageDimensionality::*;
+		matches!(self, Dim3D | Cube | Dim1DArray | Dim2DArray | CubeArray | Dim2DMultisampleArray)
+	}
+
+	/// sampler2DMS and friends, which can only be read with texelFetch.
+	pub fn is_multisampled(self) -> bool {
+		matches!(self, ImageDimensionality::Dim2DMultisample | ImageDimensionality::Dim2DMultisampleArray)
+	}
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ImageScalarType {
+	Float,
+	Int,
+	Uint,
+}
+
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct ImageBindingDescription {
+	pub kind: ImageBindingKind,
+	pub unit: u32,
+	pub dimensionality: ImageDimensionality,
+	pub scalar_type: ImageScalarType,
+	pub is_shadow: bool,
+
+	/// Format qualifier for image uniforms - e.g., gl::RGBA16F for `layout(rgba16f)`.
+	pub format: Option<u32>,
+}
+
+
 #[derive(Debug)]
 pub struct ShaderObject {
 	pub name: u32,
 	pub blocks: HashMap<String, BlockDescription>,
-	pub image_bindings: HashMap<String, u32>, // HACK: don't really care about whether they're texture or image units for now
+	pub image_bindings: HashMap<String, ImageBindingDescription>,
 	pub workgroup_size: Option<Vec3i>,
+
+	/// Every file read while compiling this shader - used for hot reloading.
+	pub dependencies: Vec<ResourcePath>,
 }
 
 
 pub fn compile_shader(resource_manager: &mut ResourceManager, def: &ShaderDef) -> anyhow::Result<ShaderObject> {
-	let content = resource_manager.load_text(&def.path)?;
+	if def.is_spirv() {
+		return spirv::load_spirv_shader(resource_manager, def);
+	}
+
+	let preprocessed = preprocess::preprocess(resource_manager, def)?;
+	let content = &preprocessed.source;
+
+	let cache_key = resource_manager.program_binary_cache.as_ref()
+		.map(|cache| cache.key(def.shader_type, content));
+
+	let cached_program_name = resource_manager.program_binary_cache.as_ref()
+		.zip(cache_key)
+		.and_then(|(cache, key)| cache.load(key));
+
+	let program_name = match cached_program_name {
+		Some(program_name) => program_name,
+		None => {
+			let program_name = compile_program(resource_manager, def, &preprocessed)?;
+
+			if let Some(cache) = &resource_manager.program_binary_cache
+				&& let Some(key) = cache_key
+			{
+				cache.store(key, program_name);
+			}
+
+			program_name
+		}
+	};
+
+	unsafe {
+		if let Some(path_str) = def.path.to_str() {
+			gl::ObjectLabel(gl::PROGRAM, program_name, path_str.len() as i32, path_str.as_ptr() as *const _);
+		}
+	}
 
-	let program_name;
+	let blocks = reflect_blocks(program_name, content)?;
+	let image_bindings = reflect_image_bindings(program_name, content)?;
 
+	Ok(ShaderObject {
+		name: program_name,
+		blocks,
+		image_bindings,
+		workgroup_size: match def.shader_type {
+			ShaderType::Compute => Some(reflect_workgroup_size(program_name)),
+			_ => None,
+		},
+		dependencies: preprocessed.source_files,
+	})
+}
+
+
+
+fn compile_program(resource_manager: &mut ResourceManager, def: &ShaderDef, preprocessed: &PreprocessedShader) -> anyhow::Result<u32> {
 	unsafe {
-		let src_cstring = std::ffi::CString::new(content.as_bytes())?;
+		let src_cstring = std::ffi::CString::new(preprocessed.source.as_bytes())?;
 		let source_strings = [
-			b"#version 450\n\0".as_ptr()  as *const i8,
 			src_cstring.as_ptr(),
 		];
 
-		program_name = gl::CreateShaderProgramv(def.shader_type as u32, source_strings.len() as _, source_strings.as_ptr());
+		let program_name = gl::CreateShaderProgramv(def.shader_type as u32, source_strings.len() as _, source_strings.as_ptr());
 
 		if program_name == 0 {
 			anyhow::bail!("Failed to create shader '{}'", def.path.display());
@@ -86,33 +262,27 @@ pub fn compile_shader(resource_manager: &mut ResourceManager, def: &ShaderDef) -
 		gl::GetProgramiv(program_name, gl::LINK_STATUS, &mut status);
 
 		if status == 0 {
-			let mut buf = [0u8; 1024];
+			let mut log_length = 0;
+			gl::GetProgramiv(program_name, gl::INFO_LOG_LENGTH, &mut log_length);
+
+			let mut buf = vec![0u8; log_length.max(1) as usize];
 			let mut len = 0;
 			gl::GetProgramInfoLog(program_name, buf.len() as _, &mut len, buf.as_mut_ptr() as _);
 
 			gl::DeleteProgram(program_name);
 
-			let error = std::str::from_utf8(&buf[..len as usize])?;
-			anyhow::bail!("Failed to create shader '{}':\n{}", def.path.display(), error);
-		}
+			let info_log = String::from_utf8_lossy(&buf[..len as usize]);
+			let mut diagnostics = diagnostics::parse_info_log(&info_log, preprocessed);
+			diagnostics::attach_snippets(resource_manager, &mut diagnostics);
 
-		if let Some(path_str) = def.path.to_str() {
-			gl::ObjectLabel(gl::PROGRAM, program_name, path_str.len() as i32, path_str.as_ptr() as *const _);
+			return Err(ShaderCompileError {
+				shader_path: def.path.clone(),
+				diagnostics,
+			}.into());
 		}
-	}
 
-	let blocks = reflect_blocks(program_name, &content)?;
-	let image_bindings = reflect_image_bindings(program_name, &content)?;
-
-	Ok(ShaderObject {
-		name: program_name,
-		blocks,
-		image_bindings,
-		workgroup_size: match def.shader_type {
-			ShaderType::Compute => Some(reflect_workgroup_size(program_name)),
-			_ => None,
-		},
-	})
+		Ok(program_name)
+	}
 }
 
 
@@ -223,7 +393,7 @@ fn buffer_block_has_readonly_keyword(name: &str, content: &str) -> bool {
 	false
 }
 
-fn reflect_image_bindings(program_name: u32, _content: &str) -> anyhow::Result<HashMap<String, u32>> {
+fn reflect_image_bindings(program_name: u32, content: &str) -> anyhow::Result<HashMap<String, ImageBindingDescription>> {
 	let mut image_bindings = HashMap::new();
 
 	let mut num_uniforms = 0;
@@ -253,83 +423,9 @@ fn reflect_image_bindings(program_name: u32, _content: &str) -> anyhow::Result<H
 		}
 
 		// Skip uniforms that aren't sampler or image types
-		// https://registry.khronos.org/OpenGL/specs/gl/glspec46.core.pdf#table.7.3
-		let image_binding_types = [
-			gl::SAMPLER_1D,
-			gl::SAMPLER_2D,
-			gl::SAMPLER_3D,
-			gl::SAMPLER_CUBE,
-			gl::SAMPLER_1D_SHADOW,
-			gl::SAMPLER_2D_SHADOW,
-			gl::SAMPLER_1D_ARRAY,
-			gl::SAMPLER_2D_ARRAY,
-			gl::SAMPLER_1D_ARRAY_SHADOW,
-			gl::SAMPLER_2D_ARRAY_SHADOW,
-			gl::SAMPLER_2D_MULTISAMPLE,
-			gl::SAMPLER_2D_MULTISAMPLE_ARRAY,
-			gl::SAMPLER_CUBE_SHADOW,
-			gl::SAMPLER_BUFFER,
-			gl::SAMPLER_2D_RECT,
-			gl::SAMPLER_2D_RECT_SHADOW,
-			gl::INT_SAMPLER_1D,
-			gl::INT_SAMPLER_2D,
-			gl::INT_SAMPLER_3D,
-			gl::INT_SAMPLER_CUBE,
-			gl::INT_SAMPLER_1D_ARRAY,
-			gl::INT_SAMPLER_2D_ARRAY,
-			gl::INT_SAMPLER_2D_MULTISAMPLE,
-			gl::INT_SAMPLER_2D_MULTISAMPLE_ARRAY,
-			gl::INT_SAMPLER_BUFFER,
-			gl::INT_SAMPLER_2D_RECT,
-			gl::UNSIGNED_INT_SAMPLER_1D,
-			gl::UNSIGNED_INT_SAMPLER_2D,
-			gl::UNSIGNED_INT_SAMPLER_3D,
-			gl::UNSIGNED_INT_SAMPLER_CUBE,
-			gl::UNSIGNED_INT_SAMPLER_1D_ARRAY,
-			gl::UNSIGNED_INT_SAMPLER_2D_ARRAY,
-			gl::UNSIGNED_INT_SAMPLER_2D_MULTISAMPLE,
-			gl::UNSIGNED_INT_SAMPLER_2D_MULTISAMPLE_ARRAY,
-			gl::UNSIGNED_INT_SAMPLER_BUFFER,
-			gl::UNSIGNED_INT_SAMPLER_2D_RECT,
-
-			gl::IMAGE_1D,
-			gl::IMAGE_2D,
-			gl::IMAGE_3D,
-			gl::IMAGE_2D_RECT,
-			gl::IMAGE_CUBE,
-			gl::IMAGE_BUFFER,
-			gl::IMAGE_1D_ARRAY,
-			gl::IMAGE_2D_ARRAY,
-			gl::IMAGE_CUBE_MAP_ARRAY,
-			gl::IMAGE_2D_MULTISAMPLE,
-			gl::IMAGE_2D_MULTISAMPLE_ARRAY,
-			gl::INT_IMAGE_1D,
-			gl::INT_IMAGE_2D,
-			gl::INT_IMAGE_3D,
-			gl::INT_IMAGE_2D_RECT,
-			gl::INT_IMAGE_CUBE,
-			gl::INT_IMAGE_BUFFER,
-			gl::INT_IMAGE_1D_ARRAY,
-			gl::INT_IMAGE_2D_ARRAY,
-			gl::INT_IMAGE_CUBE_MAP_ARRAY,
-			gl::INT_IMAGE_2D_MULTISAMPLE,
-			gl::INT_IMAGE_2D_MULTISAMPLE_ARRAY,
-			gl::UNSIGNED_INT_IMAGE_1D,
-			gl::UNSIGNED_INT_IMAGE_2D,
-			gl::UNSIGNED_INT_IMAGE_3D,
-			gl::UNSIGNED_INT_IMAGE_2D_RECT,
-			gl::UNSIGNED_INT_IMAGE_CUBE,
-			gl::UNSIGNED_INT_IMAGE_BUFFER,
-			gl::UNSIGNED_INT_IMAGE_1D_ARRAY,
-			gl::UNSIGNED_INT_IMAGE_2D_ARRAY,
-			gl::UNSIGNED_INT_IMAGE_CUBE_MAP_ARRAY,
-			gl::UNSIGNED_INT_IMAGE_2D_MULTISAMPLE,
-			gl::UNSIGNED_INT_IMAGE_2D_MULTISAMPLE_ARRAY,
-		];
-
-		if !image_binding_types.contains(&(gl_type as u32)) {
-			continue;
-		}
+		let Some(type_info) = image_binding_type_info(gl_type as u32) else {
+			continue
+		};
 
 		let mut str_buf = vec![0u8; name_length as usize];
 		unsafe {
@@ -347,12 +443,238 @@ fn reflect_image_bindings(program_name: u32, _content: &str) -> anyhow::Result<H
 			gl::GetUniformiv(program_name, location, &mut binding_index);
 		}
 
-		image_bindings.insert(name, binding_index as u32);
+		let (kind, dimensionality, scalar_type, is_shadow) = type_info;
+
+		let format = match kind {
+			ImageBindingKind::Image => image_format_qualifier(&name, content),
+			ImageBindingKind::Sampler => None,
+		};
+
+		image_bindings.insert(name, ImageBindingDescription {
+			kind,
+			unit: binding_index as u32,
+			dimensionality,
+			scalar_type,
+			is_shadow,
+			format,
+		});
 	}
 
 	Ok(image_bindings)
 }
 
+// https://registry.khronos.org/OpenGL/specs/gl/glspec46.core.pdf#table.7.3
+fn image_binding_type_info(gl_type: u32) -> Option<(ImageBindingKind, ImageDimensionality, ImageScalarType, bool)> {
+	use ImageBindingKind::*;
+	use ImageDimensionality::*;
+	use ImageScalarType::*;
+
+	let info = match gl_type {
+		gl::SAMPLER_1D => (Sampler, Dim1D, Float, false),
+		gl::SAMPLER_2D => (Sampler, Dim2D, Float, false),
+		gl::SAMPLER_3D => (Sampler, Dim3D, Float, false),
+		gl::SAMPLER_CUBE => (Sampler, Cube, Float, false),
+		gl::SAMPLER_1D_SHADOW => (Sampler, Dim1D, Float, true),
+		gl::SAMPLER_2D_SHADOW => (Sampler, Dim2D, Float, true),
+		gl::SAMPLER_1D_ARRAY => (Sampler, Dim1DArray, Float, false),
+		gl::SAMPLER_2D_ARRAY => (Sampler, Dim2DArray, Float, false),
+		gl::SAMPLER_CUBE_MAP_ARRAY => (Sampler, CubeArray, Float, false),
+		gl::SAMPLER_1D_ARRAY_SHADOW => (Sampler, Dim1DArray, Float, true),
+		gl::SAMPLER_2D_ARRAY_SHADOW => (Sampler, Dim2DArray, Float, true),
+		gl::SAMPLER_CUBE_MAP_ARRAY_SHADOW => (Sampler, CubeArray, Float, true),
+		gl::SAMPLER_2D_MULTISAMPLE => (Sampler, Dim2DMultisample, Float, false),
+		gl::SAMPLER_2D_MULTISAMPLE_ARRAY => (Sampler, Dim2DMultisampleArray, Float, false),
+		gl::SAMPLER_CUBE_SHADOW => (Sampler, Cube, Float, true),
+		gl::SAMPLER_BUFFER => (Sampler, Buffer, Float, false),
+		gl::SAMPLER_2D_RECT => (Sampler, Rect, Float, false),
+		gl::SAMPLER_2D_RECT_SHADOW => (Sampler, Rect, Float, true),
+
+		gl::INT_SAMPLER_1D => (Sampler, Dim1D, Int, false),
+		gl::INT_SAMPLER_2D => (Sampler, Dim2D, Int, false),
+		gl::INT_SAMPLER_3D => (Sampler, Dim3D, Int, false),
+		gl::INT_SAMPLER_CUBE => (Sampler, Cube, Int, false),
+		gl::INT_SAMPLER_1D_ARRAY => (Sampler, Dim1DArray, Int, false),
+		gl::INT_SAMPLER_2D_ARRAY => (Sampler, Dim2DArray, Int, false),
+		gl::INT_SAMPLER_CUBE_MAP_ARRAY => (Sampler, CubeArray, Int, false),
+		gl::INT_SAMPLER_2D_MULTISAMPLE => (Sampler, Dim2DMultisample, Int, false),
+		gl::INT_SAMPLER_2D_MULTISAMPLE_ARRAY => (Sampler, Dim2DMultisampleArray, Int, false),
+		gl::INT_SAMPLER_BUFFER => (Sampler, Buffer, Int, false),
+		gl::INT_SAMPLER_2D_RECT => (Sampler, Rect, Int, false),
+
+		gl::UNSIGNED_INT_SAMPLER_1D => (Sampler, Dim1D, Uint, false),
+		gl::UNSIGNED_INT_SAMPLER_2D => (Sampler, Dim2D, Uint, false),
+		gl::UNSIGNED_INT_SAMPLER_3D => (Sampler, Dim3D, Uint, false),
+		gl::UNSIGNED_INT_SAMPLER_CUBE => (Sampler, Cube, Uint, false),
+		gl::UNSIGNED_INT_SAMPLER_1D_ARRAY => (Sampler, Dim1DArray, Uint, false),
+		gl::UNSIGNED_INT_SAMPLER_2D_ARRAY => (Sampler, Dim2DArray, Uint, false),
+		gl::UNSIGNED_INT_SAMPLER_CUBE_MAP_ARRAY => (Sampler, CubeArray, Uint, false),
+		gl::UNSIGNED_INT_SAMPLER_2D_MULTISAMPLE => (Sampler, Dim2DMultisample, Uint, false),
+		gl::UNSIGNED_INT_SAMPLER_2D_MULTISAMPLE_ARRAY => (Sampler, Dim2DMultisampleArray, Uint, false),
+		gl::UNSIGNED_INT_SAMPLER_BUFFER => (Sampler, Buffer, Uint, false),
+		gl::UNSIGNED_INT_SAMPLER_2D_RECT => (Sampler, Rect, Uint, false),
+
+		gl::IMAGE_1D => (Image, Dim1D, Float, false),
+		gl::IMAGE_2D => (Image, Dim2D, Float, false),
+		gl::IMAGE_3D => (Image, Dim3D, Float, false),
+		gl::IMAGE_2D_RECT => (Image, Rect, Float, false),
+		gl::IMAGE_CUBE => (Image, Cube, Float, false),
+		gl::IMAGE_BUFFER => (Image, Buffer, Float, false),
+		gl::IMAGE_1D_ARRAY => (Image, Dim1DArray, Float, false),
+		gl::IMAGE_2D_ARRAY => (Image, Dim2DArray, Float, false),
+		gl::IMAGE_CUBE_MAP_ARRAY => (Image, CubeArray, Float, false),
+		gl::IMAGE_2D_MULTISAMPLE => (Image, Dim2DMultisample, Float, false),
+		gl::IMAGE_2D_MULTISAMPLE_ARRAY => (Image, Dim2DMultisampleArray, Float, false),
+
+		gl::INT_IMAGE_1D => (Image, Dim1D, Int, false),
+		gl::INT_IMAGE_2D => (Image, Dim2D, Int, false),
+		gl::INT_IMAGE_3D => (Image, Dim3D, Int, false),
+		gl::INT_IMAGE_2D_RECT => (Image, Rect, Int, false),
+		gl::INT_IMAGE_CUBE => (Image, Cube, Int, false),
+		gl::INT_IMAGE_BUFFER => (Image, Buffer, Int, false),
+		gl::INT_IMAGE_1D_ARRAY => (Image, Dim1DArray, Int, false),
+		gl::INT_IMAGE_2D_ARRAY => (Image, Dim2DArray, Int, false),
+		gl::INT_IMAGE_CUBE_MAP_ARRAY => (Image, CubeArray, Int, false),
+		gl::INT_IMAGE_2D_MULTISAMPLE => (Image, Dim2DMultisample, Int, false),
+		gl::INT_IMAGE_2D_MULTISAMPLE_ARRAY => (Image, Dim2DMultisampleArray, Int, false),
+
+		gl::UNSIGNED_INT_IMAGE_1D => (Image, Dim1D, Uint, false),
+		gl::UNSIGNED_INT_IMAGE_2D => (Image, Dim2D, Uint, false),
+		gl::UNSIGNED_INT_IMAGE_3D => (Image, Dim3D, Uint, false),
+		gl::UNSIGNED_INT_IMAGE_2D_RECT => (Image, Rect, Uint, false),
+		gl::UNSIGNED_INT_IMAGE_CUBE => (Image, Cube, Uint, false),
+		gl::UNSIGNED_INT_IMAGE_BUFFER => (Image, Buffer, Uint, false),
+		gl::UNSIGNED_INT_IMAGE_1D_ARRAY => (Image, Dim1DArray, Uint, false),
+		gl::UNSIGNED_INT_IMAGE_2D_ARRAY => (Image, Dim2DArray, Uint, false),
+		gl::UNSIGNED_INT_IMAGE_CUBE_MAP_ARRAY => (Image, CubeArray, Uint, false),
+		gl::UNSIGNED_INT_IMAGE_2D_MULTISAMPLE => (Image, Dim2DMultisample, Uint, false),
+		gl::UNSIGNED_INT_IMAGE_2D_MULTISAMPLE_ARRAY => (Image, Dim2DMultisampleArray, Uint, false),
+
+		_ => return None,
+	};
+
+	Some(info)
+}
+
+// HACK: opengl doesn't let us query the format qualifier of image uniforms, so we have to parse them out ourselves
+fn image_format_qualifier(name: &str, content: &str) -> Option<u32> {
+	for (idx, _) in content.match_indices(name) {
+		// Make sure we're looking at a whole identifier and not a prefix/suffix of one
+		let is_ident_char = |c: char| c.is_alphanumeric() || c == '_';
+		let preceded_by_ident = content[..idx].chars().next_back().map_or(false, is_ident_char);
+		let followed_by_ident = content[idx + name.len() ..].chars().next().map_or(false, is_ident_char);
+		if preceded_by_ident || followed_by_ident {
+			continue
+		}
+
+		// Find start of declaration
+		let scan_begin = content[..idx].rfind(|c: char| c == ';' || c == '}').unwrap_or(0);
+		let declaration = &content[scan_begin..idx];
+
+		if !declaration.split_whitespace().any(|keyword| keyword == "uniform") {
+			continue
+		}
+
+		let Some(layout_start) = declaration.find("layout") else {
+			return None
+		};
+
+		let layout = &declaration[layout_start + "layout".len() ..];
+		let Some((qualifiers, _)) = layout.trim_start().strip_prefix('(')
+			.and_then(|layout| layout.split_once(')')) else { return None };
+
+		return qualifiers.split(',')
+			.find_map(|qualifier| image_format_from_qualifier(qualifier.trim()));
+	}
+
+	None
+}
+
+// https://registry.khronos.org/OpenGL/specs/gl/GLSLangSpec.4.60.pdf#section.4.4.7
+fn image_format_from_qualifier(qualifier: &str) -> Option<u32> {
+	let format = match qualifier {
+		"rgba32f" => gl::RGBA32F,
+		"rgba16f" => gl::RGBA16F,
+		"rg32f" => gl::RG32F,
+		"rg16f" => gl::RG16F,
+		"r11f_g11f_b10f" => gl::R11F_G11F_B10F,
+		"r32f" => gl::R32F,
+		"r16f" => gl::R16F,
+		"rgba16" => gl::RGBA16,
+		"rgb10_a2" => gl::RGB10_A2,
+		"rgba8" => gl::RGBA8,
+		"rg16" => gl::RG16,
+		"rg8" => gl::RG8,
+		"r16" => gl::R16,
+		"r8" => gl::R8,
+		"rgba16_snorm" => gl::RGBA16_SNORM,
+		"rgba8_snorm" => gl::RGBA8_SNORM,
+		"rg16_snorm" => gl::RG16_SNORM,
+		"rg8_snorm" => gl::RG8_SNORM,
+		"r16_snorm" => gl::R16_SNORM,
+		"r8_snorm" => gl::R8_SNORM,
+
+		"rgba32i" => gl::RGBA32I,
+		"rgba16i" => gl::RGBA16I,
+		"rgba8i" => gl::RGBA8I,
+		"rg32i" => gl::RG32I,
+		"rg16i" => gl::RG16I,
+		"rg8i" => gl::RG8I,
+		"r32i" => gl::R32I,
+		"r16i" => gl::R16I,
+		"r8i" => gl::R8I,
+
+		"rgba32ui" => gl::RGBA32UI,
+		"rgba16ui" => gl::RGBA16UI,
+		"rgb10_a2ui" => gl::RGB10_A2UI,
+		"rgba8ui" => gl::RGBA8UI,
+		"rg32ui" => gl::RG32UI,
+		"rg16ui" => gl::RG16UI,
+		"rg8ui" => gl::RG8UI,
+		"r32ui" => gl::R32UI,
+		"r16ui" => gl::R16UI,
+		"r8ui" => gl::R8UI,
+
+		_ => return None,
+	};
+
+	Some(format)
+}
+
+// Image formats are compatible for load/store if they have the same texel size.
+// https://registry.khronos.org/OpenGL/specs/gl/glspec46.core.pdf#table.8.27
+pub fn image_formats_compatible(image_format: u32, qualifier_format: u32) -> bool {
+	if image_format == qualifier_format {
+		return true
+	}
+
+	fn texel_size_bits(format: u32) -> Option<u32> {
+		let size = match format {
+			gl::RGBA32F | gl::RGBA32UI | gl::RGBA32I => 128,
+
+			gl::RGBA16F | gl::RG32F | gl::RGBA16UI | gl::RG32UI | gl::RGBA16I | gl::RG32I
+				| gl::RGBA16 | gl::RGBA16_SNORM => 64,
+
+			gl::RG16F | gl::R11F_G11F_B10F | gl::R32F | gl::RGB10_A2UI | gl::RGBA8UI | gl::RG16UI
+				| gl::R32UI | gl::RGBA8I | gl::RG16I | gl::R32I | gl::RGB10_A2 | gl::RGBA8 | gl::RG16
+				| gl::RGBA8_SNORM | gl::RG16_SNORM => 32,
+
+			gl::R16F | gl::RG8UI | gl::R16UI | gl::RG8I | gl::R16I | gl::RG8 | gl::R16
+				| gl::RG8_SNORM | gl::R16_SNORM => 16,
+
+			gl::R8UI | gl::R8I | gl::R8 | gl::R8_SNORM => 8,
+
+			_ => return None,
+		};
+
+		Some(size)
+	}
+
+	match (texel_size_bits(image_format), texel_size_bits(qualifier_format)) {
+		(Some(a), Some(b)) => a == b,
+		_ => false,
+	}
+}
+
 fn reflect_workgroup_size(program_name: u32) -> Vec3i {
 	let mut workgroup_size = [0i32; 3];
 
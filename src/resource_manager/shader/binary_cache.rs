@@ -0,0 +1,128 @@
+use super::ShaderType;
+use std::path::PathBuf;
+
+
+/// Caches linked program binaries on disk so that shaders don't need to be recompiled on every startup.
+/// Keyed on the preprocessed source - which includes the GLSL version, defines and feature keys - along with
+/// the shader stage and the driver that produced the binary.
+#[derive(Debug)]
+pub struct ProgramBinaryCache {
+	directory: PathBuf,
+	driver_hash: u64,
+}
+
+impl ProgramBinaryCache {
+	pub fn new(directory: impl Into<PathBuf>) -> anyhow::Result<Self> {
+		let directory = directory.into();
+
+		let mut num_binary_formats = 0;
+		unsafe {
+			gl::GetIntegerv(gl::NUM_PROGRAM_BINARY_FORMATS, &mut num_binary_formats);
+		}
+
+		anyhow::ensure!(num_binary_formats > 0, "Driver doesn't support any program binary formats");
+
+		std::fs::create_dir_all(&directory)?;
+
+		let mut driver_hash = FNV_OFFSET_BASIS;
+
+		for name in [gl::VENDOR, gl::RENDERER, gl::VERSION, gl::SHADING_LANGUAGE_VERSION] {
+			let value = unsafe {
+				let ptr = gl::GetString(name);
+				anyhow::ensure!(!ptr.is_null(), "Failed to query driver string {name:#x}");
+				std::ffi::CStr::from_ptr(ptr as *const _)
+			};
+
+			driver_hash = fnv1a(driver_hash, value.to_bytes());
+		}
+
+		Ok(ProgramBinaryCache {
+			directory,
+			driver_hash,
+		})
+	}
+
+	pub fn key(&self, shader_type: ShaderType, source: &str) -> u64 {
+		let hash = fnv1a(self.driver_hash, &(shader_type as u32).to_le_bytes());
+		fnv1a(hash, source.as_bytes())
+	}
+
+	/// Tries to create a separable program from a cached binary. Returns None if there's no cached binary, or
+	/// if the driver rejects it - in which case the caller should fall back to compiling from source.
+	pub fn load(&self, key: u64) -> Option<u32> {
+		let data = std::fs::read(self.path_for_key(key)).ok()?;
+		if data.len() < 4 {
+			return None
+		}
+
+		let (format, binary) = data.split_at(4);
+		let format = u32::from_le_bytes(format.try_into().unwrap());
+
+		unsafe {
+			let program_name = gl::CreateProgram();
+			gl::ProgramParameteri(program_name, gl::PROGRAM_SEPARABLE, gl::TRUE as i32);
+			gl::ProgramBinary(program_name, format, binary.as_ptr() as *const _, binary.len() as i32);
+
+			let mut status = 0;
+			gl::GetProgramiv(program_name, gl::LINK_STATUS, &mut status);
+
+			if status == 0 {
+				gl::DeleteProgram(program_name);
+				return None
+			}
+
+			Some(program_name)
+		}
+	}
+
+	pub fn store(&self, key: u64, program_name: u32) {
+		// NOTE: CreateShaderProgramv doesn't give us a chance to set PROGRAM_BINARY_RETRIEVABLE_HINT before linking,
+		// but drivers return binaries regardless in practice. If one doesn't, we just don't cache.
+		let mut binary_length = 0;
+		unsafe {
+			gl::GetProgramiv(program_name, gl::PROGRAM_BINARY_LENGTH, &mut binary_length);
+		}
+
+		if binary_length <= 0 {
+			return
+		}
+
+		let mut data = vec![0u8; 4 + binary_length as usize];
+		let mut format = 0;
+		let mut length = 0;
+
+		unsafe {
+			gl::GetProgramBinary(program_name, binary_length, &mut length, &mut format, data[4..].as_mut_ptr() as *mut _);
+		}
+
+		if length <= 0 {
+			return
+		}
+
+		data.truncate(4 + length as usize);
+		data[..4].copy_from_slice(&format.to_le_bytes());
+
+		if let Err(error) = std::fs::write(self.path_for_key(key), &data) {
+			eprintln!("Failed to write program binary cache entry: {error}");
+		}
+	}
+
+	fn path_for_key(&self, key: u64) -> PathBuf {
+		self.directory.join(format!("{key:016x}.bin"))
+	}
+}
+
+
+
+// FNV-1a, since std's hashers aren't guaranteed to be stable between compiler versions
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+fn fnv1a(mut hash: u64, bytes: &[u8]) -> u64 {
+	for &byte in bytes {
+		hash ^= byte as u64;
+		hash = hash.wrapping_mul(FNV_PRIME);
+	}
+
+	hash
+}
@@ -0,0 +1,206 @@
+use crate::resource_manager::{ResourceManager, ResourcePath};
+use super::preprocess::PreprocessedShader;
+
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Severity {
+	Error,
+	Warning,
+
+	/// Log lines that couldn't be parsed into a diagnostic.
+	Info,
+}
+
+
+#[derive(Debug, Clone)]
+pub struct ShaderDiagnostic {
+	pub severity: Severity,
+
+	/// None if the diagnostic refers to the generated preamble, or if the driver didn't provide a location.
+	pub path: Option<ResourcePath>,
+	pub line: Option<u32>,
+	pub column: Option<u32>,
+
+	pub message: String,
+
+	/// The source line the diagnostic refers to, if it could be found.
+	pub snippet: Option<String>,
+}
+
+
+/// Returned by `compile_shader` on failure - can be recovered from an `anyhow::Error` with `downcast_ref`.
+#[derive(Debug)]
+pub struct ShaderCompileError {
+	pub shader_path: ResourcePath,
+	pub diagnostics: Vec<ShaderDiagnostic>,
+}
+
+impl std::error::Error for ShaderCompileError {}
+
+impl std::fmt::Display for ShaderCompileError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		writeln!(f, "Failed to compile shader '{}':", self.shader_path.display())?;
+
+		for diagnostic in self.diagnostics.iter() {
+			writeln!(f, "{diagnostic}")?;
+		}
+
+		Ok(())
+	}
+}
+
+impl std::fmt::Display for ShaderDiagnostic {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		if self.severity == Severity::Info {
+			return write!(f, "{}", self.message);
+		}
+
+		match (&self.path, self.line, self.column) {
+			(Some(path), Some(line), Some(column)) => write!(f, "{}:{line}:{column}: ", path.display())?,
+			(Some(path), Some(line), None) => write!(f, "{}:{line}: ", path.display())?,
+			(Some(path), None, _) => write!(f, "{}: ", path.display())?,
+			(None, Some(line), _) => write!(f, "<preamble>:{line}: ")?,
+			(None, None, _) => {}
+		}
+
+		let severity = match self.severity {
+			Severity::Error => "error",
+			Severity::Warning => "warning",
+			Severity::Info => unreachable!(),
+		};
+
+		write!(f, "{severity}: {}", self.message)?;
+
+		if let Some(snippet) = &self.snippet
+			&& let Some(line) = self.line
+		{
+			write!(f, "\n{line:>6} | {snippet}")?;
+		}
+
+		Ok(())
+	}
+}
+
+
+
+/// Parses a program info log into diagnostics, mapping source string numbers back to the files they came from.
+/// Line numbers don't need remapping since the preprocessor emits #line directives for every file.
+pub fn parse_info_log(info_log: &str, preprocessed: &PreprocessedShader) -> Vec<ShaderDiagnostic> {
+	let mut diagnostics = Vec::new();
+
+	for log_line in info_log.lines() {
+		let log_line = log_line.trim_end();
+		if log_line.is_empty() {
+			continue
+		}
+
+		let parsed = parse_mesa_line(log_line)
+			.or_else(|| parse_nvidia_line(log_line))
+			.or_else(|| parse_amd_line(log_line));
+
+		let Some(parsed) = parsed else {
+			diagnostics.push(ShaderDiagnostic {
+				severity: Severity::Info,
+				path: None,
+				line: None,
+				column: None,
+				message: log_line.to_owned(),
+				snippet: None,
+			});
+
+			continue
+		};
+
+		let path = preprocessed.source_file(parsed.source_string).map(|path| path.to_owned());
+
+		// Line numbers in the preamble are meaningless to the user
+		let line = path.as_ref().map(|_| parsed.line);
+
+		diagnostics.push(ShaderDiagnostic {
+			severity: parsed.severity,
+			path,
+			line,
+			column: parsed.column,
+			message: parsed.message.to_owned(),
+			snippet: None,
+		});
+	}
+
+	diagnostics
+}
+
+
+struct ParsedLine<'s> {
+	severity: Severity,
+	source_string: u32,
+	line: u32,
+	column: Option<u32>,
+	message: &'s str,
+}
+
+fn parse_severity(s: &str) -> Option<Severity> {
+	match s.trim() {
+		"error" | "ERROR" => Some(Severity::Error),
+		"warning" | "WARNING" => Some(Severity::Warning),
+		_ => None,
+	}
+}
+
+// Mesa: `0:12(5): error: message`
+fn parse_mesa_line(log_line: &str) -> Option<ParsedLine<'_>> {
+	let (source_string, rest) = log_line.split_once(':')?;
+	let (line, rest) = rest.split_once('(')?;
+	let (column, rest) = rest.split_once("):")?;
+	let (severity, message) = rest.split_once(':')?;
+
+	Some(ParsedLine {
+		severity: parse_severity(severity)?,
+		source_string: source_string.trim().parse().ok()?,
+		line: line.parse().ok()?,
+		column: Some(column.parse().ok()?),
+		message: message.trim(),
+	})
+}
+
+// NVIDIA: `0(12) : error C1008: message`
+fn parse_nvidia_line(log_line: &str) -> Option<ParsedLine<'_>> {
+	let (source_string, rest) = log_line.split_once('(')?;
+	let (line, rest) = rest.split_once(')')?;
+	let rest = rest.trim_start().strip_prefix(':')?.trim_start();
+	let (severity, message) = rest.split_once(' ')?;
+
+	Some(ParsedLine {
+		severity: parse_severity(severity)?,
+		source_string: source_string.trim().parse().ok()?,
+		line: line.parse().ok()?,
+		column: None,
+		message: message.trim(),
+	})
+}
+
+// AMD and Intel: `ERROR: 0:12: message`
+fn parse_amd_line(log_line: &str) -> Option<ParsedLine<'_>> {
+	let (severity, rest) = log_line.split_once(':')?;
+	let (source_string, rest) = rest.split_once(':')?;
+	let (line, message) = rest.split_once(':')?;
+
+	Some(ParsedLine {
+		severity: parse_severity(severity)?,
+		source_string: source_string.trim().parse().ok()?,
+		line: line.trim().parse().ok()?,
+		column: None,
+		message: message.trim(),
+	})
+}
+
+
+pub fn attach_snippets(resource_manager: &mut ResourceManager, diagnostics: &mut [ShaderDiagnostic]) {
+	for diagnostic in diagnostics.iter_mut() {
+		let (Some(path), Some(line)) = (&diagnostic.path, diagnostic.line) else { continue };
+		let Ok(content) = resource_manager.load_text(path) else { continue };
+
+		diagnostic.snippet = content.lines()
+			.nth(line.saturating_sub(1) as usize)
+			.map(|snippet| snippet.to_owned());
+	}
+}
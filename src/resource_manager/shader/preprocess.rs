@@ -0,0 +1,293 @@
+use crate::resource_manager::{ResourceManager, ResourcePath, ResourcePathRef};
+use super::{ShaderDef, ShaderType, GlslProfile};
+use anyhow::Context as _;
+use std::fmt::Write;
+
+
+/// Source string number used by the generated #version/#define preamble.
+/// Files are numbered from 1 in the order they were first included.
+pub const PREAMBLE_SOURCE_STRING: u32 = 0;
+
+
+#[derive(Debug)]
+pub struct PreprocessedShader {
+	pub source: String,
+
+	/// Every file that makes up `source`, in the order they were first included.
+	pub source_files: Vec<ResourcePath>,
+}
+
+impl PreprocessedShader {
+	/// Maps a source string number from a #line directive back to the file it came from.
+	pub fn source_file(&self, source_string: u32) -> Option<&ResourcePathRef> {
+		if source_string == PREAMBLE_SOURCE_STRING {
+			return None
+		}
+
+		self.source_files.get(source_string as usize - 1)
+			.map(|path| path.as_path())
+	}
+}
+
+
+/// Prepends #version, #defines and feature keys from `def`, and resolves #includes.
+/// Includes are first resolved relative to the including file and then relative to the resource root.
+/// Files are included every time they're referenced, so anything included more than once needs `#pragma once`
+/// or an `#ifndef` include guard. Both are evaluated by the GLSL preprocessor, so they work within `#if` blocks.
+/// Includes within comments are ignored.
+///
+/// If the root file is split into sections with `#pragma stage(vertex|fragment|compute)`, only the section matching
+/// `def.shader_type` is compiled, along with anything before the first section.
+pub fn preprocess(resource_manager: &mut ResourceManager, def: &ShaderDef) -> anyhow::Result<PreprocessedShader> {
+	let mut source = String::new();
+
+	let profile = match def.glsl_profile {
+		GlslProfile::Core => "core",
+		GlslProfile::Compatibility => "compatibility",
+	};
+
+	writeln!(source, "#version {} {profile}", def.glsl_version)?;
+
+	for (name, value) in def.defines.iter() {
+		writeln!(source, "#define {name} {value}")?;
+	}
+
+	for feature in def.features.iter() {
+		writeln!(source, "#define {feature} 1")?;
+	}
+
+	let mut preprocessor = Preprocessor {
+		resource_manager,
+		source,
+		source_files: Vec::new(),
+		include_stack: Vec::new(),
+		stage: def.shader_type,
+	};
+
+	preprocessor.process_file(&def.path)?;
+
+	Ok(PreprocessedShader {
+		source: preprocessor.source,
+		source_files: preprocessor.source_files,
+	})
+}
+
+
+
+struct Preprocessor<'rm> {
+	resource_manager: &'rm mut ResourceManager,
+	source: String,
+	source_files: Vec<ResourcePath>,
+	include_stack: Vec<ResourcePath>,
+	stage: ShaderType,
+}
+
+impl Preprocessor<'_> {
+	fn process_file(&mut self, path: &ResourcePathRef) -> anyhow::Result<()> {
+		let content = self.resource_manager.load_text(path)
+			.with_context(|| format!("Failed to load shader source '{}'", path.display()))?;
+
+		let include_once = has_pragma_once(&content);
+
+		if self.include_stack.iter().any(|including| including == path) {
+			// The guard has already been defined by the time a guarded file includes itself, so the nested copy would be empty anyway
+			anyhow::ensure!(include_once || has_include_guard(&content),
+				"'{}' includes itself without #pragma once or an include guard", path.display());
+
+			return Ok(())
+		}
+
+		let file_index = match self.source_files.iter().position(|included| included == path) {
+			Some(index) => index + 1,
+			None => {
+				self.source_files.push(path.to_owned());
+				self.source_files.len()
+			}
+		};
+
+		// GLSL doesn't support #pragma once, so emulate it with a guard that the GLSL preprocessor evaluates
+		let once_guard = format!("PREPROCESSOR_PRAGMA_ONCE_{file_index}");
+
+		if include_once {
+			writeln!(self.source, "#ifndef {once_guard}")?;
+			writeln!(self.source, "#define {once_guard}")?;
+		}
+
+		writeln!(self.source, "#line 1 {file_index}")?;
+
+		self.include_stack.push(path.to_owned());
+		self.process_lines(path, file_index, &content)?;
+		self.include_stack.pop();
+
+		if include_once {
+			writeln!(self.source, "#endif // {once_guard}")?;
+		}
+
+		Ok(())
+	}
+
+	fn process_lines(&mut self, path: &ResourcePathRef, file_index: usize, content: &str) -> anyhow::Result<()> {
+		let is_root_file = self.include_stack.len() == 1;
+		let mut current_section = None;
+		let mut has_sections = false;
+		let mut has_matching_section = false;
+
+		for (line_index, (line, directive)) in directive_lines(content).enumerate() {
+			let line_number = line_index + 1;
+
+			if let Some(stage) = parse_stage_pragma(directive) {
+				let stage = stage.with_context(|| format!("{}:{line_number}: malformed #pragma stage", path.display()))?;
+				anyhow::ensure!(is_root_file, "{}:{line_number}: #pragma stage is only allowed in the root shader file", path.display());
+
+				current_section = Some(stage);
+				has_sections = true;
+				has_matching_section |= stage == self.stage;
+
+				// Keep line numbers intact for skipped lines
+				self.source.push('\n');
+
+			} else if current_section.map_or(false, |section| section != self.stage) || is_pragma_once(directive) {
+				self.source.push('\n');
+
+			} else if let Some(include) = directive.strip_prefix("#include") {
+				let include = include.trim();
+				let Some(include) = include.strip_prefix('"').and_then(|s| s.strip_suffix('"')) else {
+					anyhow::bail!("{}:{line_number}: malformed #include directive '{include}'", path.display());
+				};
+
+				let include_path = self.resolve_include(path, include)
+					.with_context(|| format!("{}:{line_number}: couldn't resolve #include \"{include}\"", path.display()))?;
+
+				self.process_file(&include_path)?;
+
+				// Restore line numbering for the remainder of this file
+				writeln!(self.source, "#line {} {file_index}", line_number + 1)?;
+
+			} else if directive.starts_with("#version") {
+				anyhow::bail!("{}:{line_number}: #version should be specified by ShaderDef", path.display());
+
+			} else {
+				self.source.push_str(line);
+				self.source.push('\n');
+			}
+		}
+
+		if has_sections && !has_matching_section {
+			anyhow::bail!("'{}' has no section for {:?} stage", path.display(), self.stage);
+		}
+
+		Ok(())
+	}
+
+	fn resolve_include(&self, including_path: &ResourcePathRef, include: &str) -> anyhow::Result<ResourcePath> {
+		let candidates = [
+			including_path.parent().map(|parent| parent.join(include)),
+			Some(ResourcePath::from(include)),
+		];
+
+		candidates.into_iter().flatten()
+			.find(|candidate| self.resource_manager.resource_exists(candidate))
+			.ok_or_else(|| anyhow::anyhow!("File not found"))
+	}
+}
+
+
+
+/// Returns every stage declared with `#pragma stage(...)` in `content`, in order.
+pub fn declared_stages(content: &str) -> anyhow::Result<Vec<ShaderType>> {
+	let mut stages = Vec::new();
+
+	for (line_index, (_, directive)) in directive_lines(content).enumerate() {
+		let Some(stage) = parse_stage_pragma(directive) else { continue };
+		let stage = stage.with_context(|| format!("line {}: malformed #pragma stage", line_index + 1))?;
+
+		if !stages.contains(&stage) {
+			stages.push(stage);
+		}
+	}
+
+	Ok(stages)
+}
+
+fn parse_stage_pragma(directive: &str) -> Option<anyhow::Result<ShaderType>> {
+	let arguments = directive.strip_prefix("#pragma")?.trim_start().strip_prefix("stage")?.trim();
+
+	let stage = arguments.strip_prefix('(')
+		.and_then(|arguments| arguments.strip_suffix(')'))
+		.map(str::trim);
+
+	let stage = match stage {
+		Some("vertex") => Ok(ShaderType::Vertex),
+		Some("fragment") => Ok(ShaderType::Fragment),
+		Some("compute") => Ok(ShaderType::Compute),
+		_ => Err(anyhow::anyhow!("expected #pragma stage(vertex|fragment|compute), found '{directive}'")),
+	};
+
+	Some(stage)
+}
+
+
+/// Each line of `content` along with the directive it starts with, if any. Lines starting within a block comment
+/// have an empty directive, so that commented out directives are ignored.
+fn directive_lines(content: &str) -> impl Iterator<Item=(&str, &str)> {
+	let mut in_block_comment = false;
+
+	content.lines().map(move |line| {
+		let starts_in_block_comment = in_block_comment;
+		in_block_comment = ends_in_block_comment(line, in_block_comment);
+
+		match starts_in_block_comment {
+			true => (line, ""),
+			false => (line, line.trim_start()),
+		}
+	})
+}
+
+fn ends_in_block_comment(line: &str, mut in_block_comment: bool) -> bool {
+	let mut rest = line;
+
+	loop {
+		if in_block_comment {
+			let Some(end) = rest.find("*/") else { return true };
+			rest = &rest[end + 2..];
+			in_block_comment = false;
+			continue
+		}
+
+		let Some(start) = rest.find("/*") else { return false };
+
+		// A block comment opened after a line comment is part of the line comment
+		if rest.find("//").map_or(false, |line_comment| line_comment < start) {
+			return false
+		}
+
+		rest = &rest[start + 2..];
+		in_block_comment = true;
+	}
+}
+
+fn is_pragma_once(directive: &str) -> bool {
+	directive.strip_prefix("#pragma")
+		.map_or(false, |arguments| arguments.trim() == "once")
+}
+
+fn has_pragma_once(content: &str) -> bool {
+	directive_lines(content).any(|(_, directive)| is_pragma_once(directive))
+}
+
+/// Whether the first directive in `content` is `#ifndef NAME` followed by `#define NAME`.
+fn has_include_guard(content: &str) -> bool {
+	let mut directives = directive_lines(content)
+		.map(|(_, directive)| directive)
+		.filter(|directive| !directive.is_empty() && !directive.starts_with("//"));
+
+	let guard = directives.next()
+		.and_then(|directive| directive.strip_prefix("#ifndef"))
+		.map(str::trim);
+
+	let definition = directives.next()
+		.and_then(|directive| directive.strip_prefix("#define"))
+		.map(str::trim);
+
+	matches!((guard, definition), (Some(guard), Some(definition)) if !guard.is_empty() && guard == definition)
+}
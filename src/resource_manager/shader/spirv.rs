@@ -0,0 +1,661 @@
+use crate::resource_manager::ResourceManager;
+use super::{ShaderDef, ShaderType, ShaderObject, BlockDescription, BlockBindingLocation};
+use super::{ImageBindingDescription, ImageBindingKind, ImageDimensionality, ImageScalarType};
+use super::diagnostics::{ShaderCompileError, ShaderDiagnostic, Severity};
+use std::collections::{HashMap, HashSet};
+
+
+const SPIRV_MAGIC: u32 = 0x07230203;
+
+
+/// Creates a separable program from a precompiled SPIR-V module via ARB_gl_spirv.
+/// GL doesn't have to provide resource names for SPIR-V programs, so reflection is mostly done by parsing the module.
+/// Modules stripped of debug names still reflect every block and image, but under names synthesized from their
+/// bindings - e.g. `<uniform block 2>` or `<image 1>` - so they can only usefully be bound by location.
+pub fn load_spirv_shader(resource_manager: &mut ResourceManager, def: &ShaderDef) -> anyhow::Result<ShaderObject> {
+	let data = resource_manager.load_binary(&def.path)?;
+	let words = parse_words(&data)
+		.map_err(|error| anyhow::anyhow!("Failed to load SPIR-V module '{}': {error}", def.path.display()))?;
+
+	let module = SpirvModule::parse(&words)?;
+
+	let expected_execution_model = match def.shader_type {
+		ShaderType::Vertex => EXECUTION_MODEL_VERTEX,
+		ShaderType::Fragment => EXECUTION_MODEL_FRAGMENT,
+		ShaderType::Compute => EXECUTION_MODEL_GL_COMPUTE,
+	};
+
+	let has_entry_point = module.entry_points.iter()
+		.any(|(model, name)| *model == expected_execution_model && *name == def.entry_point);
+
+	anyhow::ensure!(has_entry_point, "SPIR-V module '{}' has no {:?} entry point named '{}'",
+		def.path.display(), def.shader_type, def.entry_point);
+
+	let program_name = create_program(def, &data)?;
+
+	unsafe {
+		if let Some(path_str) = def.path.to_str() {
+			gl::ObjectLabel(gl::PROGRAM, program_name, path_str.len() as i32, path_str.as_ptr() as *const _);
+		}
+	}
+
+	Ok(ShaderObject {
+		name: program_name,
+		blocks: module.reflect_blocks(program_name),
+		image_bindings: module.reflect_image_bindings(),
+		workgroup_size: match def.shader_type {
+			ShaderType::Compute => Some(super::reflect_workgroup_size(program_name)),
+			_ => None,
+		},
+		dependencies: vec![def.path.clone()],
+	})
+}
+
+
+fn create_program(def: &ShaderDef, data: &[u8]) -> anyhow::Result<u32> {
+	let entry_point = std::ffi::CString::new(def.entry_point.as_bytes())?;
+	let (constant_ids, constant_values): (Vec<u32>, Vec<u32>) = def.specialization_constants.iter().copied().unzip();
+
+	unsafe {
+		let shader_name = gl::CreateShader(def.shader_type as u32);
+		gl::ShaderBinary(1, &shader_name, gl::SHADER_BINARY_FORMAT_SPIR_V, data.as_ptr() as *const _, data.len() as i32);
+		gl::SpecializeShader(shader_name, entry_point.as_ptr(), constant_ids.len() as u32,
+			constant_ids.as_ptr(), constant_values.as_ptr());
+
+		let mut status = 0;
+		gl::GetShaderiv(shader_name, gl::COMPILE_STATUS, &mut status);
+
+		if status == 0 {
+			let mut log_length = 0;
+			gl::GetShaderiv(shader_name, gl::INFO_LOG_LENGTH, &mut log_length);
+
+			let mut buf = vec![0u8; log_length.max(1) as usize];
+			let mut len = 0;
+			gl::GetShaderInfoLog(shader_name, buf.len() as _, &mut len, buf.as_mut_ptr() as _);
+
+			gl::DeleteShader(shader_name);
+
+			let info_log = String::from_utf8_lossy(&buf[..len as usize]);
+			return Err(compile_error(def, &info_log).into());
+		}
+
+		let program_name = gl::CreateProgram();
+		gl::ProgramParameteri(program_name, gl::PROGRAM_SEPARABLE, gl::TRUE as i32);
+		gl::AttachShader(program_name, shader_name);
+		gl::LinkProgram(program_name);
+		gl::DetachShader(program_name, shader_name);
+		gl::DeleteShader(shader_name);
+
+		gl::GetProgramiv(program_name, gl::LINK_STATUS, &mut status);
+
+		if status == 0 {
+			let mut log_length = 0;
+			gl::GetProgramiv(program_name, gl::INFO_LOG_LENGTH, &mut log_length);
+
+			let mut buf = vec![0u8; log_length.max(1) as usize];
+			let mut len = 0;
+			gl::GetProgramInfoLog(program_name, buf.len() as _, &mut len, buf.as_mut_ptr() as _);
+
+			gl::DeleteProgram(program_name);
+
+			let info_log = String::from_utf8_lossy(&buf[..len as usize]);
+			return Err(compile_error(def, &info_log).into());
+		}
+
+		Ok(program_name)
+	}
+}
+
+// SPIR-V has no meaningful source locations to map back to, so each log line becomes its own diagnostic
+fn compile_error(def: &ShaderDef, info_log: &str) -> ShaderCompileError {
+	let diagnostics = info_log.lines()
+		.map(str::trim)
+		.filter(|line| !line.is_empty())
+		.map(|line| ShaderDiagnostic {
+			severity: Severity::Error,
+			path: Some(def.path.clone()),
+			line: None,
+			column: None,
+			message: line.to_owned(),
+			snippet: None,
+		})
+		.collect();
+
+	ShaderCompileError {
+		shader_path: def.path.clone(),
+		diagnostics,
+	}
+}
+
+
+fn parse_words(data: &[u8]) -> anyhow::Result<Vec<u32>> {
+	anyhow::ensure!(data.len() % 4 == 0 && data.len() >= 20, "Not a SPIR-V module");
+
+	let mut words: Vec<u32> = data.chunks_exact(4)
+		.map(|chunk| u32::from_le_bytes(chunk.try_into().unwrap()))
+		.collect();
+
+	if words[0] == SPIRV_MAGIC.swap_bytes() {
+		for word in words.iter_mut() {
+			*word = word.swap_bytes();
+		}
+	}
+
+	anyhow::ensure!(words[0] == SPIRV_MAGIC, "Not a SPIR-V module");
+
+	Ok(words)
+}
+
+
+
+// https://registry.khronos.org/SPIR-V/specs/unified1/SPIRV.html
+const OP_NAME: u32 = 5;
+const OP_ENTRY_POINT: u32 = 15;
+const OP_TYPE_INT: u32 = 21;
+const OP_TYPE_FLOAT: u32 = 22;
+const OP_TYPE_IMAGE: u32 = 25;
+const OP_TYPE_SAMPLED_IMAGE: u32 = 27;
+const OP_TYPE_ARRAY: u32 = 28;
+const OP_TYPE_RUNTIME_ARRAY: u32 = 29;
+const OP_TYPE_POINTER: u32 = 32;
+const OP_VARIABLE: u32 = 59;
+const OP_DECORATE: u32 = 71;
+const OP_MEMBER_DECORATE: u32 = 72;
+
+const DECORATION_BLOCK: u32 = 2;
+const DECORATION_BUFFER_BLOCK: u32 = 3;
+const DECORATION_NON_WRITABLE: u32 = 24;
+const DECORATION_BINDING: u32 = 33;
+
+const STORAGE_CLASS_UNIFORM_CONSTANT: u32 = 0;
+const STORAGE_CLASS_UNIFORM: u32 = 2;
+const STORAGE_CLASS_STORAGE_BUFFER: u32 = 12;
+
+const EXECUTION_MODEL_VERTEX: u32 = 0;
+const EXECUTION_MODEL_FRAGMENT: u32 = 4;
+const EXECUTION_MODEL_GL_COMPUTE: u32 = 5;
+
+const IMAGE_SAMPLED_STORAGE: u32 = 2;
+
+
+#[derive(Debug, Copy, Clone)]
+struct ImageType {
+	sampled_type: u32,
+	dim: u32,
+	depth: u32,
+	arrayed: u32,
+	multisampled: u32,
+	sampled: u32,
+	format: u32,
+}
+
+#[derive(Debug, Default)]
+struct SpirvModule {
+	entry_points: Vec<(u32, String)>,
+
+	names: HashMap<u32, String>,
+	bindings: HashMap<u32, u32>,
+	block_structs: HashSet<u32>,
+	buffer_block_structs: HashSet<u32>,
+	non_writable: HashSet<u32>,
+
+	scalar_types: HashMap<u32, ImageScalarType>,
+	image_types: HashMap<u32, ImageType>,
+	sampled_image_types: HashMap<u32, u32>,
+	array_types: HashMap<u32, u32>,
+	pointer_types: HashMap<u32, u32>,
+
+	// (id, pointer type, storage class)
+	variables: Vec<(u32, u32, u32)>,
+}
+
+impl SpirvModule {
+	fn parse(words: &[u32]) -> anyhow::Result<SpirvModule> {
+		let mut module = SpirvModule::default();
+
+		// Skip header
+		let mut cursor = 5;
+
+		while cursor < words.len() {
+			let word_count = (words[cursor] >> 16) as usize;
+			let opcode = words[cursor] & 0xffff;
+
+			anyhow::ensure!(word_count > 0 && cursor + word_count <= words.len(), "Malformed SPIR-V instruction");
+
+			let operands = &words[cursor + 1 .. cursor + word_count];
+			cursor += word_count;
+
+			match (opcode, operands) {
+				(OP_NAME, [target, name @ ..]) => {
+					module.names.insert(*target, parse_string(name));
+				}
+
+				(OP_ENTRY_POINT, [model, _function, name @ ..]) => {
+					module.entry_points.push((*model, parse_string(name)));
+				}
+
+				(OP_TYPE_INT, [id, _width, signedness]) => {
+					let scalar_type = match *signedness {
+						0 => ImageScalarType::Uint,
+						_ => ImageScalarType::Int,
+					};
+
+					module.scalar_types.insert(*id, scalar_type);
+				}
+
+				(OP_TYPE_FLOAT, [id, ..]) => {
+					module.scalar_types.insert(*id, ImageScalarType::Float);
+				}
+
+				(OP_TYPE_IMAGE, [id, sampled_type, dim, depth, arrayed, multisampled, sampled, format, ..]) => {
+					module.image_types.insert(*id, ImageType {
+						sampled_type: *sampled_type,
+						dim: *dim,
+						depth: *depth,
+						arrayed: *arrayed,
+						multisampled: *multisampled,
+						sampled: *sampled,
+						format: *format,
+					});
+				}
+
+				(OP_TYPE_SAMPLED_IMAGE, [id, image_type]) => {
+					module.sampled_image_types.insert(*id, *image_type);
+				}
+
+				(OP_TYPE_ARRAY, [id, element_type, _]) | (OP_TYPE_RUNTIME_ARRAY, [id, element_type]) => {
+					module.array_types.insert(*id, *element_type);
+				}
+
+				(OP_TYPE_POINTER, [id, _storage_class, pointee_type]) => {
+					module.pointer_types.insert(*id, *pointee_type);
+				}
+
+				(OP_VARIABLE, [pointer_type, id, storage_class, ..]) => {
+					module.variables.push((*id, *pointer_type, *storage_class));
+				}
+
+				(OP_DECORATE, [target, decoration, literals @ ..]) => match (*decoration, literals) {
+					(DECORATION_BLOCK, _) => { module.block_structs.insert(*target); }
+					(DECORATION_BUFFER_BLOCK, _) => { module.buffer_block_structs.insert(*target); }
+					(DECORATION_NON_WRITABLE, _) => { module.non_writable.insert(*target); }
+					(DECORATION_BINDING, [binding]) => { module.bindings.insert(*target, *binding); }
+					_ => {}
+				}
+
+				// glslang decorates each member of a readonly buffer block rather than the block itself
+				(OP_MEMBER_DECORATE, [target, _member, DECORATION_NON_WRITABLE]) => {
+					module.non_writable.insert(*target);
+				}
+
+				_ => {}
+			}
+		}
+
+		Ok(module)
+	}
+
+	// Strips pointer and array types to get at the underlying type of a variable
+	fn variable_type(&self, pointer_type: u32) -> u32 {
+		let mut ty = self.pointer_types.get(&pointer_type).copied().unwrap_or(pointer_type);
+
+		while let Some(&element_type) = self.array_types.get(&ty) {
+			ty = element_type;
+		}
+
+		ty
+	}
+
+	fn reflect_blocks(&self, program_name: u32) -> HashMap<String, BlockDescription> {
+		// Sizes are the one thing GL can tell us reliably about SPIR-V blocks
+		let uniform_block_sizes = query_block_sizes(program_name, gl::UNIFORM_BLOCK);
+		let storage_block_sizes = query_block_sizes(program_name, gl::SHADER_STORAGE_BLOCK);
+
+		self.block_descriptions(&uniform_block_sizes, &storage_block_sizes)
+	}
+
+	fn block_descriptions(&self, uniform_block_sizes: &HashMap<u32, u32>, storage_block_sizes: &HashMap<u32, u32>)
+		-> HashMap<String, BlockDescription>
+	{
+		let mut blocks = HashMap::new();
+
+		for &(id, pointer_type, storage_class) in self.variables.iter() {
+			let struct_type = self.variable_type(pointer_type);
+			let binding = self.bindings.get(&id).copied().unwrap_or(0);
+
+			let is_ssbo = match storage_class {
+				STORAGE_CLASS_STORAGE_BUFFER => true,
+				STORAGE_CLASS_UNIFORM if self.buffer_block_structs.contains(&struct_type) => true,
+				STORAGE_CLASS_UNIFORM if self.block_structs.contains(&struct_type) => false,
+				_ => continue,
+			};
+
+			// Block names are attached to the struct type, the variable name is the (optional) instance name
+			let name = self.name(struct_type).or_else(|| self.name(id))
+				.map_or_else(|| synthesized_block_name(is_ssbo, binding), String::from);
+
+			let (binding_location, total_size) = match is_ssbo {
+				true => (BlockBindingLocation::Ssbo(binding), storage_block_sizes.get(&binding)),
+				false => (BlockBindingLocation::Ubo(binding), uniform_block_sizes.get(&binding)),
+			};
+
+			let is_readonly = self.non_writable.contains(&id) || self.non_writable.contains(&struct_type);
+
+			blocks.insert(name, BlockDescription {
+				binding_location,
+				total_size: total_size.copied().unwrap_or(0),
+				is_read_write: is_ssbo && !is_readonly,
+			});
+		}
+
+		blocks
+	}
+
+	fn reflect_image_bindings(&self) -> HashMap<String, ImageBindingDescription> {
+		let mut image_bindings = HashMap::new();
+
+		for &(id, pointer_type, storage_class) in self.variables.iter() {
+			if storage_class != STORAGE_CLASS_UNIFORM_CONSTANT {
+				continue
+			}
+
+			let ty = self.variable_type(pointer_type);
+
+			let (kind, image_type) = if let Some(image_type) = self.sampled_image_types.get(&ty) {
+				(ImageBindingKind::Sampler, *image_type)
+			} else {
+				(ImageBindingKind::Image, ty)
+			};
+
+			let Some(image_type) = self.image_types.get(&image_type) else { continue };
+
+			if kind == ImageBindingKind::Image && image_type.sampled != IMAGE_SAMPLED_STORAGE {
+				continue
+			}
+
+			let Some(dimensionality) = spirv_dimensionality(image_type) else { continue };
+
+			let unit = self.bindings.get(&id).copied().unwrap_or(0);
+			let name = self.name(id)
+				.map_or_else(|| synthesized_image_name(kind, unit), String::from);
+
+			image_bindings.insert(name, ImageBindingDescription {
+				kind,
+				unit,
+				dimensionality,
+				scalar_type: self.scalar_types.get(&image_type.sampled_type).copied().unwrap_or(ImageScalarType::Float),
+				is_shadow: image_type.depth == 1,
+				format: match kind {
+					ImageBindingKind::Image => spirv_image_format(image_type.format),
+					ImageBindingKind::Sampler => None,
+				},
+			});
+		}
+
+		image_bindings
+	}
+
+	// Anonymous blocks and stripped modules have empty or missing names
+	fn name(&self, id: u32) -> Option<&str> {
+		self.names.get(&id)
+			.map(String::as_str)
+			.filter(|name| !name.is_empty())
+	}
+}
+
+
+fn synthesized_block_name(is_ssbo: bool, binding: u32) -> String {
+	match is_ssbo {
+		true => format!("<storage block {binding}>"),
+		false => format!("<uniform block {binding}>"),
+	}
+}
+
+fn synthesized_image_name(kind: ImageBindingKind, unit: u32) -> String {
+	match kind {
+		ImageBindingKind::Sampler => format!("<sampler {unit}>"),
+		ImageBindingKind::Image => format!("<image {unit}>"),
+	}
+}
+
+
+fn parse_string(words: &[u32]) -> String {
+	let bytes: Vec<u8> = words.iter()
+		.flat_map(|word| word.to_le_bytes())
+		.take_while(|&byte| byte != 0)
+		.collect();
+
+	String::from_utf8_lossy(&bytes).into_owned()
+}
+
+fn query_block_sizes(program_name: u32, interface: u32) -> HashMap<u32, u32> {
+	let mut sizes = HashMap::new();
+	let mut num_blocks = 0;
+
+	unsafe {
+		gl::GetProgramInterfaceiv(program_name, interface, gl::ACTIVE_RESOURCES, &mut num_blocks);
+	}
+
+	let property_names = [gl::BUFFER_BINDING, gl::BUFFER_DATA_SIZE];
+
+	for block_idx in 0..num_blocks {
+		let mut result = [0; 2];
+
+		unsafe {
+			gl::GetProgramResourceiv(
+				program_name, interface,
+				block_idx as u32,
+				property_names.len() as _, property_names.as_ptr(),
+				result.len() as _, std::ptr::null_mut(), result.as_mut_ptr());
+		}
+
+		let [buffer_binding, buffer_data_size] = result;
+		sizes.insert(buffer_binding as u32, buffer_data_size as u32);
+	}
+
+	sizes
+}
+
+fn spirv_dimensionality(image_type: &ImageType) -> Option<ImageDimensionality> {
+	use ImageDimensionality::*;
+
+	let is_arrayed = image_type.arrayed != 0;
+	let is_multisampled = image_type.multisampled != 0;
+
+	let dimensionality = match (image_type.dim, is_arrayed, is_multisampled) {
+		(0, false, _) => Dim1D,
+		(0, true, _) => Dim1DArray,
+		(1, false, false) => Dim2D,
+		(1, true, false) => Dim2DArray,
+		(1, false, true) => Dim2DMultisample,
+		(1, true, true) => Dim2DMultisampleArray,
+		(2, _, _) => Dim3D,
+		(3, false, _) => Cube,
+		(3, true, _) => CubeArray,
+		(4, _, _) => Rect,
+		(5, _, _) => Buffer,
+		_ => return None,
+	};
+
+	Some(dimensionality)
+}
+
+// https://registry.khronos.org/SPIR-V/specs/unified1/SPIRV.html#Image_Format
+fn spirv_image_format(format: u32) -> Option<u32> {
+	let format = match format {
+		1 => gl::RGBA32F,
+		2 => gl::RGBA16F,
+		3 => gl::R32F,
+		4 => gl::RGBA8,
+		5 => gl::RGBA8_SNORM,
+		6 => gl::RG32F,
+		7 => gl::RG16F,
+		8 => gl::R11F_G11F_B10F,
+		9 => gl::R16F,
+		10 => gl::RGBA16,
+		11 => gl::RGB10_A2,
+		12 => gl::RG16,
+		13 => gl::RG8,
+		14 => gl::R16,
+		15 => gl::R8,
+		16 => gl::RGBA16_SNORM,
+		17 => gl::RG16_SNORM,
+		18 => gl::RG8_SNORM,
+		19 => gl::R16_SNORM,
+		20 => gl::R8_SNORM,
+		21 => gl::RGBA32I,
+		22 => gl::RGBA16I,
+		23 => gl::RGBA8I,
+		24 => gl::R32I,
+		25 => gl::RG32I,
+		26 => gl::RG16I,
+		27 => gl::RG8I,
+		28 => gl::R16I,
+		29 => gl::R8I,
+		30 => gl::RGBA32UI,
+		31 => gl::RGBA16UI,
+		32 => gl::RGBA8UI,
+		33 => gl::R32UI,
+		34 => gl::RGB10_A2UI,
+		35 => gl::RG32UI,
+		36 => gl::RG16UI,
+		37 => gl::RG8UI,
+		38 => gl::R16UI,
+		39 => gl::R8UI,
+		_ => return None,
+	};
+
+	Some(format)
+}
+
+
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	// Hand assembled equivalent of:
+	//
+	//   layout(local_size_x = 1) in;
+	//   layout(binding = 1) uniform Constants { vec4 tint; };
+	//   layout(binding = 2) readonly buffer Input { float values[]; } input_data;
+	//   layout(binding = 3) buffer Output { float results[]; };
+	//   layout(binding = 4) uniform sampler2DArray u_texture;
+	//   layout(binding = 5, rgba8) uniform writeonly image2D u_image;
+	//   void main() {}
+	const REFLECTION_MODULE: &[u8] = include_bytes!("test_data/reflection.comp.spv");
+
+	fn parse_fixture(strip_names: bool) -> SpirvModule {
+		let mut words = parse_words(REFLECTION_MODULE).unwrap();
+
+		if strip_names {
+			let mut stripped = words[..5].to_vec();
+			let mut cursor = 5;
+
+			while cursor < words.len() {
+				let word_count = (words[cursor] >> 16) as usize;
+
+				if words[cursor] & 0xffff != OP_NAME {
+					stripped.extend_from_slice(&words[cursor .. cursor + word_count]);
+				}
+
+				cursor += word_count;
+			}
+
+			words = stripped;
+		}
+
+		SpirvModule::parse(&words).unwrap()
+	}
+
+	#[test]
+	fn rejects_non_spirv_data() {
+		assert!(parse_words(&[0; 20]).is_err());
+		assert!(parse_words(&REFLECTION_MODULE[..18]).is_err());
+	}
+
+	#[test]
+	fn parses_byte_swapped_modules() {
+		let swapped: Vec<u8> = REFLECTION_MODULE.chunks_exact(4)
+			.flat_map(|chunk| [chunk[3], chunk[2], chunk[1], chunk[0]])
+			.collect();
+
+		assert_eq!(parse_words(&swapped).unwrap(), parse_words(REFLECTION_MODULE).unwrap());
+	}
+
+	#[test]
+	fn parses_entry_points() {
+		let module = parse_fixture(false);
+		assert_eq!(module.entry_points, [(EXECUTION_MODEL_GL_COMPUTE, String::from("main"))]);
+	}
+
+	#[test]
+	fn reflects_blocks() {
+		let module = parse_fixture(false);
+		let storage_block_sizes = HashMap::from([(3, 64)]);
+		let blocks = module.block_descriptions(&HashMap::new(), &storage_block_sizes);
+
+		assert_eq!(blocks.len(), 3);
+
+		assert_eq!(blocks["Constants"], BlockDescription {
+			binding_location: BlockBindingLocation::Ubo(1),
+			total_size: 0,
+			is_read_write: false,
+		});
+
+		assert_eq!(blocks["Input"], BlockDescription {
+			binding_location: BlockBindingLocation::Ssbo(2),
+			total_size: 0,
+			is_read_write: false,
+		});
+
+		assert_eq!(blocks["Output"], BlockDescription {
+			binding_location: BlockBindingLocation::Ssbo(3),
+			total_size: 64,
+			is_read_write: true,
+		});
+	}
+
+	#[test]
+	fn reflects_image_bindings() {
+		let module = parse_fixture(false);
+		let image_bindings = module.reflect_image_bindings();
+
+		assert_eq!(image_bindings.len(), 2);
+
+		assert_eq!(image_bindings["u_texture"], ImageBindingDescription {
+			kind: ImageBindingKind::Sampler,
+			unit: 4,
+			dimensionality: ImageDimensionality::Dim2DArray,
+			scalar_type: ImageScalarType::Float,
+			is_shadow: false,
+			format: None,
+		});
+
+		assert_eq!(image_bindings["u_image"], ImageBindingDescription {
+			kind: ImageBindingKind::Image,
+			unit: 5,
+			dimensionality: ImageDimensionality::Dim2D,
+			scalar_type: ImageScalarType::Float,
+			is_shadow: false,
+			format: Some(gl::RGBA8),
+		});
+	}
+
+	#[test]
+	fn synthesizes_names_for_stripped_modules() {
+		let module = parse_fixture(true);
+		assert!(module.names.is_empty());
+
+		let blocks = module.block_descriptions(&HashMap::new(), &HashMap::new());
+		let mut block_names: Vec<_> = blocks.keys().map(String::as_str).collect();
+		block_names.sort();
+
+		assert_eq!(block_names, ["<storage block 2>", "<storage block 3>", "<uniform block 1>"]);
+		assert_eq!(blocks["<storage block 3>"].binding_location, BlockBindingLocation::Ssbo(3));
+
+		let image_bindings = module.reflect_image_bindings();
+		assert_eq!(image_bindings["<sampler 4>"].unit, 4);
+		assert_eq!(image_bindings["<image 5>"].format, Some(gl::RGBA8));
+	}
+}
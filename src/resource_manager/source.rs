@@ -0,0 +1,202 @@
+use crate::resource_manager::{ResourcePath, ResourcePathRef};
+use anyhow::Context;
+use std::collections::{HashMap, HashSet};
+use std::time::SystemTime;
+
+
+/// Somewhere resources can be loaded from. Paths are always relative to the root of the source.
+pub trait ResourceSource: std::fmt::Debug {
+	fn contains(&self, path: &ResourcePathRef) -> bool;
+	fn load(&self, path: &ResourcePathRef) -> anyhow::Result<Vec<u8>>;
+
+	/// Files directly within `directory`, relative to the root of the source. Empty if `directory` isn't in this source.
+	fn list_directory(&self, directory: &ResourcePathRef) -> Vec<ResourcePath>;
+
+	/// None if the source can't change while running, in which case resources from it are never hot reloaded.
+	fn modification_time(&self, _path: &ResourcePathRef) -> Option<SystemTime> {
+		None
+	}
+}
+
+
+/// Loads from a directory on disk.
+#[derive(Debug)]
+pub struct DirectorySource {
+	root: ResourcePath,
+}
+
+impl DirectorySource {
+	pub fn new(root: impl Into<ResourcePath>) -> DirectorySource {
+		DirectorySource { root: root.into() }
+	}
+}
+
+impl ResourceSource for DirectorySource {
+	fn contains(&self, path: &ResourcePathRef) -> bool {
+		self.root.join(path).exists()
+	}
+
+	fn load(&self, path: &ResourcePathRef) -> anyhow::Result<Vec<u8>> {
+		let full_path = self.root.join(path);
+		std::fs::read(&full_path)
+			.with_context(|| format!("Failed to read '{}'", full_path.display()))
+	}
+
+	fn list_directory(&self, directory: &ResourcePathRef) -> Vec<ResourcePath> {
+		let Ok(entries) = std::fs::read_dir(self.root.join(directory)) else {
+			return Vec::new()
+		};
+
+		entries.filter_map(Result::ok)
+			.filter(|entry| entry.file_type().map_or(false, |ty| ty.is_file()))
+			.map(|entry| directory.join(entry.file_name()))
+			.collect()
+	}
+
+	fn modification_time(&self, path: &ResourcePathRef) -> Option<SystemTime> {
+		std::fs::metadata(self.root.join(path)).ok()?
+			.modified().ok()
+	}
+}
+
+
+/// Resources compiled into the binary - see `embed_resources!`.
+#[derive(Debug, Default)]
+pub struct EmbeddedSource {
+	files: HashMap<String, &'static [u8]>,
+}
+
+impl EmbeddedSource {
+	pub fn new() -> EmbeddedSource {
+		EmbeddedSource::default()
+	}
+
+	pub fn with_file(mut self, path: impl AsRef<ResourcePathRef>, data: &'static [u8]) -> EmbeddedSource {
+		self.files.insert(normalize_path(path.as_ref()), data);
+		self
+	}
+}
+
+impl ResourceSource for EmbeddedSource {
+	fn contains(&self, path: &ResourcePathRef) -> bool {
+		self.files.contains_key(&normalize_path(path))
+	}
+
+	fn load(&self, path: &ResourcePathRef) -> anyhow::Result<Vec<u8>> {
+		self.files.get(&normalize_path(path))
+			.map(|data| data.to_vec())
+			.with_context(|| format!("'{}' isn't embedded", path.display()))
+	}
+
+	fn list_directory(&self, directory: &ResourcePathRef) -> Vec<ResourcePath> {
+		files_in_directory(self.files.keys(), directory)
+	}
+}
+
+/// Builds an `EmbeddedSource` from paths relative to the crate's `resource` directory.
+///
+/// ```ignore
+/// resource_manager.add_source(embed_resources!["shaders/common/util.glsl", "images/coolcat.png"]);
+/// ```
+#[macro_export]
+macro_rules! embed_resources {
+	($($path:literal),* $(,)?) => {
+		$crate::resource_manager::EmbeddedSource::new()
+			$(.with_file($path, include_bytes!(concat!(env!("CARGO_MANIFEST_DIR"), "/resource/", $path))))*
+	};
+}
+
+
+/// Loads from a zip archive. Entries are read on demand, so the archive stays open for the lifetime of the source.
+pub struct ArchiveSource {
+	path: std::path::PathBuf,
+	entries: HashSet<String>,
+	archive: std::sync::Mutex<zip::ZipArchive<std::io::BufReader<std::fs::File>>>,
+}
+
+impl ArchiveSource {
+	pub fn open(path: impl Into<std::path::PathBuf>) -> anyhow::Result<ArchiveSource> {
+		let path = path.into();
+
+		let file = std::fs::File::open(&path)
+			.with_context(|| format!("Failed to open archive '{}'", path.display()))?;
+
+		let archive = zip::ZipArchive::new(std::io::BufReader::new(file))
+			.with_context(|| format!("Failed to read archive '{}'", path.display()))?;
+
+		let entries = archive.file_names()
+			.filter(|name| !name.ends_with('/'))
+			.map(String::from)
+			.collect();
+
+		Ok(ArchiveSource {
+			path,
+			entries,
+			archive: std::sync::Mutex::new(archive),
+		})
+	}
+}
+
+impl std::fmt::Debug for ArchiveSource {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		f.debug_struct("ArchiveSource")
+			.field("path", &self.path)
+			.field("entries", &self.entries.len())
+			.finish()
+	}
+}
+
+impl ResourceSource for ArchiveSource {
+	fn contains(&self, path: &ResourcePathRef) -> bool {
+		self.entries.contains(&normalize_path(path))
+	}
+
+	fn load(&self, path: &ResourcePathRef) -> anyhow::Result<Vec<u8>> {
+		use std::io::Read;
+
+		let mut archive = self.archive.lock().unwrap();
+		let mut entry = archive.by_name(&normalize_path(path))
+			.with_context(|| format!("'{}' isn't in archive '{}'", path.display(), self.path.display()))?;
+
+		let mut data = Vec::with_capacity(entry.size() as usize);
+		entry.read_to_end(&mut data)?;
+		Ok(data)
+	}
+
+	fn list_directory(&self, directory: &ResourcePathRef) -> Vec<ResourcePath> {
+		files_in_directory(self.entries.iter(), directory)
+	}
+}
+
+
+
+/// Forward slash separated, with `.` and `..` resolved - which is how embedded files and archive entries are keyed.
+fn normalize_path(path: &ResourcePathRef) -> String {
+	use std::path::Component;
+
+	let mut components = Vec::new();
+
+	for component in path.components() {
+		match component {
+			Component::Normal(part) => components.push(part.to_string_lossy()),
+			Component::ParentDir => { components.pop(); }
+			_ => {}
+		}
+	}
+
+	components.join("/")
+}
+
+fn files_in_directory<'a>(files: impl Iterator<Item=&'a String>, directory: &ResourcePathRef) -> Vec<ResourcePath> {
+	let directory = normalize_path(directory);
+
+	files.filter_map(|file| {
+			let name = match directory.is_empty() {
+				true => file.as_str(),
+				false => file.strip_prefix(&directory)?.strip_prefix('/')?,
+			};
+
+			(!name.contains('/')).then(|| ResourcePath::from(file))
+		})
+		.collect()
+}
@@ -159,6 +159,23 @@ impl UploadHeap {
 
 
 
+impl Drop for UploadHeap {
+	fn drop(&mut self) {
+		unsafe {
+			for range in self.locked_ranges.drain(..) {
+				gl::ClientWaitSync(range.fence, gl::SYNC_FLUSH_COMMANDS_BIT, u64::MAX);
+				gl::DeleteSync(range.fence);
+			}
+
+			gl::UnmapNamedBuffer(self.buffer_name);
+			gl::DeleteBuffers(1, &self.buffer_name);
+		}
+	}
+}
+
+
+
+
 #[derive(Debug)]
 struct LockedRange {
 	fence: gl::types::GLsync,